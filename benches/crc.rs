@@ -0,0 +1,21 @@
+//! Benchmarks for the CRC-16 implementation.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ndstrim::crc;
+
+fn bench_checksum(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc::checksum");
+
+    for size in [0x15e, 0x1000, 0x10000, 0x100000] {
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| crc::checksum(black_box(data)));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_checksum);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! Generator for synthetic NDS ROMs.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ndstrim::crc;
+use ndstrim::nds::NINTENDO_LOGO;
+
+/// Size of a NTR/TWL header.
+const HEADER_SIZE: usize = 0x1000;
+
+/// Builds an NTR-only ROM declaring `rom_size` bytes of data, padded with `0xff` to `file_size`.
+pub fn build_rom(rom_size: u32, file_size: usize) -> Vec<u8> {
+    let mut rom = vec![0xff; file_size];
+
+    let header = &mut rom[..HEADER_SIZE];
+    header.fill(0);
+    header[..12].copy_from_slice(b"NDSTRIMBENCH");
+    header[0x0c..0x10].copy_from_slice(b"NTRE");
+    header[0x10..0x12].copy_from_slice(b"01");
+    header[0x80..0x84].copy_from_slice(&rom_size.to_le_bytes());
+    header[0x84..0x88].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+    header[0xc0..0x15c].copy_from_slice(&NINTENDO_LOGO);
+    header[0x15c..0x15e].copy_from_slice(&crc::checksum(&NINTENDO_LOGO).to_le_bytes());
+    let header_crc = crc::checksum(&header[..0x15e]);
+    header[0x15e..0x160].copy_from_slice(&header_crc.to_le_bytes());
+
+    // Fill the ROM data with a non-pad pattern.
+    for (i, byte) in rom[HEADER_SIZE..rom_size as usize].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    rom
+}
+
+/// Writes the ROM produced by [`build_rom`] to `path`.
+pub fn write_rom(path: &Path, rom_size: u32, file_size: usize) -> io::Result<()> {
+    fs::write(path, build_rom(rom_size, file_size))
+}
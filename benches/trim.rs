@@ -0,0 +1,36 @@
+//! Benchmarks for trimming operations.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ndstrim::NdsFile;
+
+mod fixture;
+
+fn bench_trim_with_name(c: &mut Criterion) {
+    let mut group = c.benchmark_group("NdsFile::trim_with_name");
+    group.sample_size(20);
+
+    let dir = env::temp_dir();
+    for rom_size in [0x10_0000_u32, 0x100_0000] {
+        let src = dir.join(format!("ndstrim-bench-{}-{rom_size}.nds", process::id()));
+        let dest = src.with_extension("trim.nds");
+        fixture::write_rom(&src, rom_size, rom_size as usize * 2).unwrap();
+
+        let mut ndsfile = NdsFile::open(&src).unwrap();
+        group.throughput(Throughput::Bytes(rom_size.into()));
+        group.bench_function(BenchmarkId::from_parameter(rom_size), |b| {
+            b.iter(|| ndsfile.trim_with_name(&dest).unwrap());
+        });
+
+        fs::remove_file(&src).unwrap();
+        fs::remove_file(&dest).unwrap();
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_trim_with_name);
+criterion_main!(benches);
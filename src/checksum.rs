@@ -0,0 +1,94 @@
+//! Checksums of trimmed ROMs, written in files next to them.
+
+#![warn(clippy::pedantic)]
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use sha1::{Digest, Sha1};
+
+use ndstrim::crc::{Crc16, Crc32};
+
+/// The algorithms checksums can be computed with.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Algorithm {
+    /// The CRC-16 used in Nintendo DS headers
+    Crc16,
+    /// CRC-32, as used by No-Intro DATs
+    Crc32,
+    /// SHA-1
+    Sha1,
+}
+
+impl Algorithm {
+    /// Returns the extension appended to checksum files.
+    fn extension(self) -> &'static str {
+        match self {
+            Algorithm::Crc16 => "crc16",
+            Algorithm::Crc32 => "crc32",
+            Algorithm::Sha1 => "sha1",
+        }
+    }
+}
+
+/// A checksum in the making, fed with the data written to it.
+pub enum Hasher {
+    Crc16(Crc16),
+    Crc32(Crc32),
+    Sha1(Sha1),
+}
+
+impl Hasher {
+    /// Creates a checksum computed with `algorithm`.
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Crc16 => Hasher::Crc16(Crc16::new()),
+            Algorithm::Crc32 => Hasher::Crc32(Crc32::new()),
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+        }
+    }
+
+    /// Returns the checksum of the data written so far, in lowercase hexadecimal.
+    pub fn finalize(self) -> String {
+        match self {
+            Hasher::Crc16(crc) => format!("{:04x}", crc.finalize()),
+            Hasher::Crc32(crc) => format!("{:08x}", crc.finalize()),
+            Hasher::Sha1(sha1) => format!("{:x}", sha1.finalize()),
+        }
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Hasher::Crc16(crc) => crc.update(buf),
+            Hasher::Crc32(crc) => crc.update(buf),
+            Hasher::Sha1(sha1) => sha1.update(buf),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes `checksum`, computed over `dest` with `algorithm`, next to `dest`, returning the path of
+/// the checksum file.
+///
+/// The file holds a single line in the format of `sha1sum` and similar tools, so that it can be
+/// verified with them.
+pub fn write(dest: &Path, algorithm: Algorithm, checksum: &str) -> io::Result<PathBuf> {
+    let mut path = OsString::from(dest);
+    path.push(".");
+    path.push(algorithm.extension());
+    let path = PathBuf::from(path);
+
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(&path, format!("{checksum}  {name}\n"))?;
+
+    Ok(path)
+}
@@ -3,26 +3,579 @@
 #![warn(clippy::pedantic)]
 
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+
+use crate::checksum;
+use crate::sidecar;
+use ndstrim::nds::{self, Rounding};
 
 /// Command-line arguments.
 #[derive(Parser)]
+#[allow(clippy::struct_excessive_bools)]
 #[command(author, version, about)]
 pub struct Cli {
     /// ROM files to trim
-    #[arg(required = true)]
+    #[arg(required_unless_present_any = ["self_test", "files_from"])]
     pub files: Vec<PathBuf>,
 
+    /// Also trim the paths and glob patterns listed in a file, one per line
+    #[arg(long, value_name = "FILE")]
+    pub files_from: Option<PathBuf>,
+
+    /// Look for ROMs in directories and their subdirectories
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Maximum depth of subdirectories to look into, 0 meaning none
+    #[arg(long, value_name = "N", requires = "recursive")]
+    pub max_depth: Option<usize>,
+
+    /// Descend into symbolically linked directories
+    #[arg(long, requires = "recursive")]
+    pub follow_symlinks: bool,
+
+    /// Choose which of the ROMs found to trim from a list
+    #[arg(long)]
+    pub interactive: bool,
+
     /// Simulate execution, don't trim
     #[arg(short, long)]
     pub simulate: bool,
 
     /// Extension for trimmed files
-    #[arg(short, long, default_value_t = String::from("trim.nds"))]
+    #[arg(short, long, env = "NDSTRIM_EXTENSION", default_value_t = String::from("trim.nds"))]
     pub extension: String,
 
+    /// Also process files that look like trimmed copies, e.g. "foo.trim.nds"
+    #[arg(long)]
+    pub include_trimmed: bool,
+
     /// Trim files in-place
     #[arg(short, long)]
     pub inplace: bool,
+
+    /// Rename files trimmed in-place after a template, e.g. "{gamecode} - {title}"
+    #[arg(long, value_name = "TEMPLATE", requires = "inplace")]
+    pub rename: Option<String>,
+
+    /// Directory for trimmed files
+    #[arg(short, long, conflicts_with = "inplace")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Recreate the directory structure of the input directories in the output directory
+    #[arg(long, requires_all = ["recursive", "output_dir"])]
+    pub preserve_tree: bool,
+
+    /// What to do when a trimmed copy already exists
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = Overwrite::Always, conflicts_with = "inplace")]
+    pub overwrite: Overwrite,
+
+    /// Write trimmed files atomically through a temporary file
+    #[arg(short, long, conflicts_with = "inplace")]
+    pub atomic: bool,
+
+    /// Resume interrupted copies, keeping the part already written to existing trimmed files
+    #[arg(long, conflicts_with_all = ["inplace", "atomic", "overwrite", "sparse"])]
+    pub resume: bool,
+
+    /// With --resume, check the part already written against the ROM and rewrite it if it differs
+    #[arg(long, requires = "resume")]
+    pub checksum: bool,
+
+    /// Write trimmed files as sparse files, skipping chunks of zeros, which only saves space on
+    /// filesystems with holes, e.g. ext4, Btrfs, XFS and APFS but not NTFS
+    #[arg(long, conflicts_with = "inplace")]
+    pub sparse: bool,
+
+    /// Copy the permissions and extended attributes of the original files to trimmed copies
+    #[arg(long, conflicts_with = "inplace")]
+    pub preserve_perms: bool,
+
+    /// Permission bits of trimmed files and backups, in octal, e.g. 644
+    #[arg(long, value_name = "OCTAL", value_parser = parse_mode, conflicts_with_all = ["inplace", "preserve_perms"])]
+    pub mode: Option<u32>,
+
+    /// Mark trimmed files as read-only
+    #[arg(long)]
+    pub make_readonly: bool,
+
+    /// Compare the trimmed data against an existing file, don't trim
+    #[arg(long, value_name = "FILE")]
+    pub compare: Option<PathBuf>,
+
+    /// Look for further ROMs following the first one in each file
+    #[arg(short, long, conflicts_with_all = ["inplace", "compare"])]
+    pub multirom: bool,
+
+    /// Never trim files listed in the file allocation table, even if the header says otherwise
+    #[arg(short, long)]
+    pub tight: bool,
+
+    /// Accept homebrew ROMs with an invalid Nintendo logo
+    #[arg(long)]
+    pub homebrew: bool,
+
+    /// Accept ROMs whose header CRC is stale but whose Nintendo logo is valid, e.g. patched ones
+    #[arg(long)]
+    pub accept_stale_crc: bool,
+
+    /// Fix stale header CRCs while trimming in-place
+    #[arg(long, requires = "inplace")]
+    pub fix_header_crc: bool,
+
+    /// Offset of the ROM within each file
+    #[arg(long, value_name = "BYTES", default_value_t = 0, value_parser = parse_bytes)]
+    pub rom_offset: u64,
+
+    /// Search the first bytes of each file for the header, for damaged dumps
+    #[arg(long, value_name = "BYTES", conflicts_with = "rom_offset", value_parser = parse_bytes)]
+    pub scan_header: Option<u64>,
+
+    /// Trim ROMs to the sizes listed by header CRC-32 in a file, as "crc=size" lines
+    #[arg(long, value_name = "FILE")]
+    pub size_map: Option<PathBuf>,
+
+    /// Trim ROMs to the sizes listed by serial in a database, as "serial=size" lines
+    #[arg(long, value_name = "FILE")]
+    pub db: Option<PathBuf>,
+
+    /// Skip ROMs whose game code is listed in a file
+    #[arg(long, value_name = "FILE")]
+    pub skip_gamecodes: Option<PathBuf>,
+
+    /// Only trim ROMs whose game code is listed in a file
+    #[arg(long, value_name = "FILE")]
+    pub only_gamecodes: Option<PathBuf>,
+
+    /// Skip files modified more recently than this, e.g. "30m" or "1h", such as partial downloads
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_age: Option<Duration>,
+
+    /// What to do with trimmed copies identical to one written earlier in the run
+    #[arg(long, value_enum, value_name = "MODE", conflicts_with_all = ["inplace", "resume", "emit_checksum"])]
+    pub dedupe: Option<Dedupe>,
+
+    /// Write a checksum of each trimmed file next to it, computed while copying
+    #[arg(long, value_enum, value_name = "ALGORITHM", conflicts_with_all = ["inplace", "resume"])]
+    pub emit_checksum: Option<checksum::Algorithm>,
+
+    /// Save the data removed from each ROM to a .tail file in a directory
+    #[arg(long, value_name = "DIR")]
+    pub save_tail: Option<PathBuf>,
+
+    /// Write a sidecar file with metadata next to each trimmed file
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub sidecar: Option<sidecar::Format>,
+
+    /// Only print the trimmed size of each file in bytes, don't trim
+    #[arg(long, conflicts_with_all = ["inplace", "compare", "multirom"])]
+    pub print_size: bool,
+
+    /// Only print the sections of each ROM described by its header, marking those trimming discards
+    #[arg(long, conflicts_with_all = ["inplace", "compare", "multirom", "print_size", "check"])]
+    pub preview_layout: bool,
+
+    /// Triage files without trimming them, classifying each as a retail ROM, homebrew, already
+    /// trimmed, corrupt or not a ROM, and failing if any is corrupt
+    #[arg(long, conflicts_with_all = ["inplace", "compare", "multirom", "print_size", "preview_layout", "check"])]
+    pub triage: bool,
+
+    /// Only print the used size, cartridge capacity and file size of each ROM, flagging odd dumps
+    #[arg(long, conflicts_with_all = ["inplace", "compare", "multirom", "print_size", "preview_layout", "triage", "check"])]
+    pub capacity_report: bool,
+
+    /// Only print the trimmed size of each ROM next to the one of trimmers that only keep the size
+    /// declared in the header
+    #[arg(long, conflicts_with_all = ["inplace", "compare", "multirom", "print_size", "preview_layout", "triage", "check", "capacity_report"])]
+    pub compare_tools: bool,
+
+    /// Only check whether ROMs are already trimmed, failing if any isn't
+    #[arg(long, conflicts_with_all = ["inplace", "compare", "multirom", "print_size"])]
+    pub check: bool,
+
+    /// Ignore ROMs that could be trimmed by at most this many bytes when checking
+    #[arg(long, value_name = "BYTES", default_value_t = 0, value_parser = parse_bytes, requires = "check")]
+    pub min_savings: u64,
+
+    /// Don't report ROMs that are already trimmed, only count them in the summary
+    #[arg(long)]
+    pub hide_already_trimmed: bool,
+
+    /// Print details about each ROM, such as where its RSA certificate lies
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Print the CRC-32 of the trimmed data
+    #[arg(long)]
+    pub print_crc32: bool,
+
+    /// Print a fingerprint of the trimmed data, its CRC-32, along with its size
+    #[arg(long)]
+    pub fingerprint: bool,
+
+    /// Only remove zero padding past the cartridge capacity from dumps that have it, keeping them at
+    /// their full size
+    #[arg(long)]
+    pub strip_over_padding: bool,
+
+    /// Print the byte values found in the removed data
+    #[arg(long)]
+    pub scan_padding: bool,
+
+    /// Refuse to trim ROMs unless the removed data is all padding
+    #[arg(long)]
+    pub require_reversible: bool,
+
+    /// Trim ROMs even if the removed data isn't all padding
+    #[arg(long, requires = "require_reversible")]
+    pub force: bool,
+
+    /// The units to print sizes in
+    #[arg(long, value_enum, value_name = "UNITS", default_value_t = Units::Bytes)]
+    pub units: Units,
+
+    /// Print the amount of files trimmed and bytes saved, grouped by a key
+    #[arg(long, value_enum, value_name = "KEY")]
+    pub summary_by: Option<SummaryKey>,
+
+    /// Write a JSON report of the whole run to a file once done
+    #[arg(long, value_name = "FILE")]
+    pub summary_json: Option<PathBuf>,
+
+    /// Append the outcome of every file to a log, one JSON object per line
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Write the outcome of every file to a status file in a directory
+    #[arg(long, value_name = "DIR")]
+    pub status_dir: Option<PathBuf>,
+
+    /// Run a command after each trim, e.g. "reindex {dest} {saved}"
+    #[arg(long, value_name = "CMD", value_parser = parse_hook)]
+    pub post_hook: Option<Hook>,
+
+    /// What to do when the post-hook fails
+    #[arg(long, value_enum, value_name = "ACTION", default_value_t = HookFailure::Warn, requires = "post_hook")]
+    pub hook_failure: HookFailure,
+
+    /// Treat suspicious ROMs as errors instead of warning about them
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Warn about ROMs whose No-Intro style file name disagrees with their header
+    #[arg(long)]
+    pub check_naming: bool,
+
+    /// Warn about ROMs whose declared size isn't a multiple of this
+    #[arg(long, value_name = "BYTES", default_value_t = 512, value_parser = parse_alignment)]
+    pub alignment: u64,
+
+    /// Treat a Nintendo logo that isn't byte-for-byte canonical as an error
+    #[arg(long)]
+    pub strict_logo: bool,
+
+    /// Report which checks an invalid header fails, to tell patched ROMs from corrupt ones
+    #[arg(long)]
+    pub detect_patches: bool,
+
+    /// Read defaults from this configuration file instead of looking for ndstrim.toml
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore configuration files
+    #[arg(long, conflicts_with = "config")]
+    pub no_config: bool,
+
+    /// When to color output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Open up to this many files ahead on another thread, for high-latency storage
+    #[arg(long, value_name = "FILES", default_value_t = 0)]
+    pub read_ahead: usize,
+
+    /// Size of the buffers used to copy data
+    #[arg(long, value_name = "BYTES", default_value_t = nds::DEFAULT_BUFFER_SIZE, value_parser = parse_buffer_size)]
+    pub buffer_size: usize,
+
+    /// How to finalize the trimmed size: exact, cert, sector:BYTES or pow2
+    #[arg(long, value_name = "POLICY", default_value = "cert", value_parser = parse_rounding, conflicts_with = "no_cert")]
+    pub rounding: Rounding,
+
+    /// Keep this many bytes past the trimmed size, e.g. for a footer appended to a trimmed ROM
+    #[arg(long, value_name = "BYTES", default_value_t = 0, value_parser = parse_bytes)]
+    pub reserve_trailing: u64,
+
+    /// Don't preserve the RSA certificate, may break Download Play
+    #[arg(long)]
+    pub no_cert: bool,
+
+    /// Check the checksum implementations against known vectors and exit
+    #[arg(long, hide = true, exclusive = true)]
+    pub self_test: bool,
+}
+
+/// When to color output.
+#[derive(Clone, Copy, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorChoice {
+    /// Color output if it's a terminal and `NO_COLOR` isn't set
+    Auto,
+    /// Always color output
+    Always,
+    /// Never color output
+    Never,
+}
+
+/// How to deduplicate identical trimmed copies.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Dedupe {
+    /// Hard link duplicates to the first copy, copying where links aren't supported
+    Hardlink,
+}
+
+/// The units to print sizes in.
+#[derive(Clone, Copy, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Units {
+    /// Bytes
+    Bytes,
+    /// Binary multiples of bytes, e.g. "1.5 MiB"
+    Iec,
+    /// Megabits, as cartridge sizes are given, where 1 Mbit is 128 KiB
+    Mbit,
+}
+
+impl Units {
+    /// Formats `bytes` as a size, e.g. "size reduced from 64 Mbit to 33.5 Mbit".
+    ///
+    /// Sizes in bytes are bare numbers. Other sizes are whole numbers if they're exact, and are
+    /// otherwise rounded to the nearest tenth, with halves rounded up.
+    pub fn size(self, bytes: u64) -> String {
+        const MBIT: u64 = 128 * 1024;
+
+        match self {
+            Units::Bytes => bytes.to_string(),
+            Units::Iec => {
+                let (unit, name) = [(1 << 30, "GiB"), (1 << 20, "MiB"), (1 << 10, "KiB")]
+                    .into_iter()
+                    .find(|&(unit, _)| bytes >= unit)
+                    .unwrap_or((1, "B"));
+                format!("{} {name}", scale(bytes, unit))
+            }
+            Units::Mbit => format!("{} Mbit", scale(bytes, MBIT)),
+        }
+    }
+
+    /// Formats `bytes` as an amount, e.g. "1024 bytes" rather than the bare number of
+    /// [`Units::size`].
+    pub fn amount(self, bytes: u64) -> String {
+        match self {
+            Units::Bytes => format!("{bytes} bytes"),
+            _ => self.size(bytes),
+        }
+    }
+}
+
+/// Divides `bytes` by `unit`, rounding to the nearest tenth unless the result is whole.
+fn scale(bytes: u64, unit: u64) -> String {
+    if bytes.is_multiple_of(unit) {
+        return (bytes / unit).to_string();
+    }
+
+    let tenths = (u128::from(bytes) * 10 + u128::from(unit) / 2) / u128::from(unit);
+    format!("{}.{}", tenths / 10, tenths % 10)
+}
+
+/// What to do when a trimmed copy already exists.
+#[derive(Clone, Copy, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overwrite {
+    /// Replace it
+    Always,
+    /// Keep it and skip the ROM
+    Never,
+    /// Replace it only if the new copy is smaller
+    IfSmaller,
+    /// Rename it with a .bak suffix, then write the new copy
+    Backup,
+}
+
+/// What to do when the post-hook fails.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookFailure {
+    /// Report the failure and go on
+    Warn,
+    /// Report the failure and stop the run
+    Abort,
+}
+
+/// Keys to group the summary of trimmed files by.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SummaryKey {
+    /// The region derived from the game code
+    Region,
+}
+
+/// A command to run after each trim, split into words.
+#[derive(Clone)]
+pub struct Hook(pub Vec<String>);
+
+/// Parses a hook command, splitting it into words like a POSIX shell would.
+fn parse_hook(s: &str) -> Result<Hook, String> {
+    let words = shell_words::split(s).map_err(|e| format!("{e}"))?;
+    if words.is_empty() {
+        return Err("empty command".to_owned());
+    }
+
+    Ok(Hook(words))
+}
+
+/// Parses an amount of bytes, either in decimal or in hexadecimal with a `0x` prefix.
+pub fn parse_bytes(s: &str) -> Result<u64, String> {
+    let result = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    result.map_err(|e| format!("{e}"))
+}
+
+/// Parses a duration made of a whole number and an optional unit, `s`, `m`, `h` or `d`.
+///
+/// Numbers without a unit are in seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number.parse().map_err(|e| format!("{e}"))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit '{unit}', expected s, m, h or d")),
+    };
+
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| "duration too long".to_owned())
+}
+
+/// Parses Unix permission bits in octal, optionally prefixed with `0o`, ensuring that the owner
+/// can read the file.
+fn parse_mode(s: &str) -> Result<u32, String> {
+    let mode =
+        u32::from_str_radix(s.strip_prefix("0o").unwrap_or(s), 8).map_err(|e| format!("{e}"))?;
+    if mode > 0o7777 {
+        return Err("must be at most 7777".to_owned());
+    }
+    if mode & 0o400 == 0 {
+        return Err("must let the owner read the file".to_owned());
+    }
+
+    Ok(mode)
+}
+
+/// Parses a rounding policy, `exact`, `cert`, `pow2` or `sector:` followed by a non-zero amount of
+/// bytes.
+fn parse_rounding(s: &str) -> Result<Rounding, String> {
+    match s {
+        "exact" => Ok(Rounding::Exact),
+        "cert" => Ok(Rounding::Cert),
+        "pow2" => Ok(Rounding::Pow2),
+        _ => match s.strip_prefix("sector:").map(parse_bytes) {
+            Some(Ok(0)) => Err("sector size must not be zero".to_owned()),
+            Some(sector) => sector.map(Rounding::Sector),
+            None => Err("expected exact, cert, sector:BYTES or pow2".to_owned()),
+        },
+    }
+}
+
+/// Parses an alignment, ensuring that it's not zero.
+fn parse_alignment(s: &str) -> Result<u64, String> {
+    match parse_bytes(s)? {
+        0 => Err("must be at least 1".to_owned()),
+        alignment => Ok(alignment),
+    }
+}
+
+/// Parses a buffer size, ensuring that it's not smaller than [`nds::MIN_BUFFER_SIZE`].
+fn parse_buffer_size(s: &str) -> Result<usize, String> {
+    let size = usize::try_from(parse_bytes(s)?).map_err(|e| format!("{e}"))?;
+    if size < nds::MIN_BUFFER_SIZE {
+        return Err(format!("must be at least {}", nds::MIN_BUFFER_SIZE));
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use clap::{CommandFactory, FromArgMatches};
+
+    use super::*;
+
+    #[test]
+    fn extension_prefers_flag_then_environment_then_default() {
+        // Variables only read by this test stand in for NDSTRIM_EXTENSION, which other tests
+        // parsing arguments concurrently rely on being left alone.
+        env::set_var("NDSTRIM_TEST_EXTENSION", "min.nds");
+        let extension = |var: &'static str, args: &[&str]| {
+            let matches = Cli::command()
+                .mut_arg("extension", |a| a.env(var))
+                .try_get_matches_from(["ndstrim", "foo.nds"].iter().chain(args))
+                .unwrap();
+            Cli::from_arg_matches(&matches).unwrap().extension
+        };
+
+        assert_eq!(extension("NDSTRIM_TEST_EXTENSION_UNSET", &[]), "trim.nds");
+        assert_eq!(extension("NDSTRIM_TEST_EXTENSION", &[]), "min.nds");
+        assert_eq!(
+            extension("NDSTRIM_TEST_EXTENSION", &["--extension", "cut.nds"]),
+            "cut.nds"
+        );
+    }
+
+    #[test]
+    fn sizes_are_formatted_in_units() {
+        assert_eq!(Units::Bytes.size(1536), "1536");
+        assert_eq!(Units::Bytes.amount(1536), "1536 bytes");
+        assert_eq!(Units::Iec.size(512), "512 B");
+        assert_eq!(Units::Iec.size(1536), "1.5 KiB");
+        assert_eq!(Units::Iec.size(64 << 20), "64 MiB");
+        assert_eq!(Units::Iec.amount(1 << 30), "1 GiB");
+        assert_eq!(Units::Mbit.size(8 << 20), "64 Mbit");
+        assert_eq!(Units::Mbit.size(0x10d_0000), "134.5 Mbit");
+        // 1.25 KiB is rounded half up.
+        assert_eq!(Units::Iec.size(1280), "1.3 KiB");
+    }
+
+    #[test]
+    fn rounding_is_parsed() {
+        assert_eq!(parse_rounding("exact"), Ok(Rounding::Exact));
+        assert_eq!(parse_rounding("cert"), Ok(Rounding::Cert));
+        assert_eq!(parse_rounding("pow2"), Ok(Rounding::Pow2));
+        assert_eq!(parse_rounding("sector:512"), Ok(Rounding::Sector(512)));
+        assert!(parse_rounding("sector:0").is_err());
+        assert!(parse_rounding("sector").is_err());
+    }
+
+    #[test]
+    fn hook_failure_requires_post_hook() {
+        assert!(Cli::try_parse_from(["ndstrim", "--hook-failure", "abort", "foo.nds"]).is_err());
+        let cli = Cli::try_parse_from(["ndstrim", "--post-hook", "true", "foo.nds"]).unwrap();
+        assert!(cli.hook_failure == HookFailure::Warn);
+    }
+
+    #[test]
+    fn triage_conflicts_with_trimming_modes() {
+        assert!(Cli::try_parse_from(["ndstrim", "--triage", "foo.nds"]).unwrap().triage);
+        assert!(Cli::try_parse_from(["ndstrim", "--triage", "--check", "foo.nds"]).is_err());
+        assert!(Cli::try_parse_from(["ndstrim", "--check-only", "foo.nds"]).is_err());
+    }
 }
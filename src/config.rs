@@ -0,0 +1,205 @@
+//! Persistent preferences, loaded from a configuration file.
+
+#![warn(clippy::pedantic)]
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::parser::ValueSource;
+use clap::{Arg, ArgMatches, CommandFactory, FromArgMatches};
+use serde::Deserialize;
+
+use crate::cli::{Cli, ColorChoice, Overwrite, Units};
+
+/// The name of the configuration file.
+const FILE_NAME: &str = "ndstrim.toml";
+
+/// Defaults for command-line arguments, named after their long flags.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    extension: Option<String>,
+    output_dir: Option<PathBuf>,
+    overwrite: Option<Overwrite>,
+    atomic: Option<bool>,
+    sparse: Option<bool>,
+    preserve_perms: Option<bool>,
+    make_readonly: Option<bool>,
+    tight: Option<bool>,
+    homebrew: Option<bool>,
+    no_cert: Option<bool>,
+    strict: Option<bool>,
+    alignment: Option<u64>,
+    min_savings: Option<u64>,
+    hide_already_trimmed: Option<bool>,
+    units: Option<Units>,
+    color: Option<ColorChoice>,
+    buffer_size: Option<usize>,
+}
+
+impl Config {
+    /// Loads the configuration file at `path`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Returns the path of the configuration file in use, if any.
+    ///
+    /// `ndstrim.toml` in the current directory takes precedence over the one in the user's
+    /// configuration directory.
+    pub fn find() -> Option<PathBuf> {
+        let local = PathBuf::from(FILE_NAME);
+        if local.is_file() {
+            return Some(local);
+        }
+
+        let global = user_config_dir()?.join("ndstrim").join(FILE_NAME);
+        global.is_file().then_some(global)
+    }
+
+    /// Fills in the fields of `cli` that weren't set on the command line or through the
+    /// environment, as recorded in `matches`.
+    ///
+    /// Clap only checks the arguments it parses for conflicts, so a configured value is also
+    /// skipped if its argument conflicts with one that was set, e.g. `overwrite` with `--resume`.
+    fn apply(self, cli: &mut Cli, matches: &ArgMatches) {
+        let command = Cli::command();
+        let explicit: Vec<&Arg> = command
+            .get_arguments()
+            .filter(|arg| {
+                matches.ids().any(|id| id == arg.get_id())
+                    && !matches!(
+                        matches.value_source(arg.get_id().as_str()),
+                        None | Some(ValueSource::DefaultValue)
+                    )
+            })
+            .collect();
+        // Clap records each conflict on one side only.
+        let conflicting = |a: &Arg, b: &Arg| {
+            command.get_arg_conflicts_with(a).contains(&b)
+                || command.get_arg_conflicts_with(b).contains(&a)
+        };
+        let configurable = |id: &str| {
+            command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .is_some_and(|arg| explicit.iter().all(|e| *e != arg && !conflicting(arg, e)))
+        };
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        if configurable(stringify!($field)) {
+                            cli.$field = value;
+                        }
+                    }
+                )*
+            };
+        }
+
+        merge!(
+            extension,
+            overwrite,
+            atomic,
+            sparse,
+            preserve_perms,
+            make_readonly,
+            tight,
+            homebrew,
+            no_cert,
+            strict,
+            alignment,
+            min_savings,
+            hide_already_trimmed,
+            units,
+            color,
+            buffer_size,
+        );
+        if let Some(dir) = self.output_dir {
+            if configurable("output_dir") {
+                cli.output_dir = Some(dir);
+            }
+        }
+    }
+}
+
+/// Parses the command-line arguments, using the configuration file, if any, for defaults.
+///
+/// The configuration file is the one passed with `--config`, or the one found by
+/// [`Config::find`], unless `--no-config` is passed.
+///
+/// Values given on the command line or through the environment take precedence over the
+/// configuration file, which takes precedence over the built-in defaults. Exits on failure.
+pub fn parse_args() -> Cli {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let path = if cli.no_config {
+        None
+    } else {
+        cli.config.clone().or_else(Config::find)
+    };
+    if let Some(path) = path {
+        match Config::load(&path) {
+            Ok(config) => config.apply(&mut cli, &matches),
+            Err(e) => {
+                eprintln!("'{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+
+    cli
+}
+
+/// Returns the user's configuration directory, following the conventions of the platform.
+fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(PathBuf::from);
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `args` with `config` as the contents of the configuration file.
+    fn parse(config: &str, args: &[&str]) -> Cli {
+        let matches = Cli::command()
+            .try_get_matches_from(["ndstrim", "foo.nds"].iter().chain(args))
+            .unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        toml::from_str::<Config>(config).unwrap().apply(&mut cli, &matches);
+        cli
+    }
+
+    #[test]
+    fn command_line_takes_precedence() {
+        let cli = parse("extension = \"min.nds\"\natomic = true", &["--extension", "cut.nds"]);
+        assert_eq!(cli.extension, "cut.nds");
+        assert!(cli.atomic);
+    }
+
+    #[test]
+    fn conflicting_values_are_skipped() {
+        let cli = parse("overwrite = \"backup\"", &["--resume"]);
+        assert!(matches!(cli.overwrite, Overwrite::Always));
+
+        let cli = parse("no-cert = true", &["--rounding", "sector:512"]);
+        assert!(!cli.no_cert);
+
+        let cli = parse("output-dir = \"out\"\natomic = true", &["--inplace"]);
+        assert_eq!(cli.output_dir, None);
+        assert!(!cli.atomic);
+    }
+}
@@ -10,25 +10,134 @@
 /// # Examples
 ///
 /// ```
-/// use nds::crc;
+/// use ndstrim::crc;
 ///
 /// let data = vec![0xde, 0xad, 0xbe, 0xef];
 /// let checksum = crc::checksum(&data);
 /// ```
+#[must_use]
 pub fn checksum(data: &[u8]) -> u16 {
-    const POLYNOMIAL: u16 = 0xa001;
-
-    let mut crc = 0xffff;
-    for byte in data {
-        crc ^= u16::from(*byte);
-        for _ in 0..8 {
-            let carry = (crc & 0x1) > 0;
-            crc >>= 1;
-            if carry {
-                crc ^= POLYNOMIAL;
+    let mut crc = Crc16::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+/// A streaming Nintendo DS CRC-16 checksum, for data too large to hold in memory.
+///
+/// # Examples
+///
+/// ```
+/// use ndstrim::crc::{self, Crc16};
+///
+/// let mut crc = Crc16::new();
+/// crc.update(&[0xde, 0xad]);
+/// crc.update(&[0xbe, 0xef]);
+/// assert_eq!(crc.finalize(), crc::checksum(&[0xde, 0xad, 0xbe, 0xef]));
+/// ```
+pub struct Crc16 {
+    state: u16,
+}
+
+impl Default for Crc16 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc16 {
+    /// Creates a new checksum.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { state: 0xffff }
+    }
+
+    /// Feeds `data` into `self`.
+    pub fn update(&mut self, data: &[u8]) {
+        const POLYNOMIAL: u16 = 0xa001;
+
+        for byte in data {
+            self.state ^= u16::from(*byte);
+            for _ in 0..8 {
+                let carry = (self.state & 0x1) > 0;
+                self.state >>= 1;
+                if carry {
+                    self.state ^= POLYNOMIAL;
+                }
+            }
+        }
+    }
+
+    /// Returns the checksum of the data fed so far.
+    #[must_use]
+    pub fn finalize(&self) -> u16 {
+        self.state
+    }
+}
+
+/// A streaming CRC-32 (IEEE 802.3) checksum, as used by No-Intro DATs and ZIP archives.
+///
+/// # Examples
+///
+/// ```
+/// use ndstrim::crc::Crc32;
+///
+/// let mut crc = Crc32::new();
+/// crc.update(&[0xde, 0xad]);
+/// crc.update(&[0xbe, 0xef]);
+/// let checksum = crc.finalize();
+/// ```
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Lookup table for the reflected polynomial `0xedb88320`.
+    const TABLE: [u32; 256] = {
+        const POLYNOMIAL: u32 = 0xedb8_8320;
+
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            #[allow(clippy::cast_possible_truncation)]
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 0x1 > 0 {
+                    (crc >> 1) ^ POLYNOMIAL
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
             }
+            table[i] = crc;
+            i += 1;
         }
+        table
+    };
+
+    /// Creates a new checksum.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { state: 0xffff_ffff }
     }
 
-    crc
+    /// Feeds `data` into `self`.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            let index = (self.state ^ u32::from(*byte)) & 0xff;
+            self.state = (self.state >> 8) ^ Self::TABLE[index as usize];
+        }
+    }
+
+    /// Returns the checksum of the data fed so far.
+    #[must_use]
+    pub fn finalize(&self) -> u32 {
+        !self.state
+    }
 }
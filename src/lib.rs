@@ -0,0 +1,37 @@
+//! Library to trim Nintendo DS(i) ROMs.
+//!
+//! ROMs are opened as [`NdsFile`]s, which compute how much of the file is padding and can be
+//! trimmed in-place or into a copy.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//! use ndstrim::NdsFile;
+//!
+//! let src = PathBuf::from("foo.nds");
+//! let mut ndsfile = NdsFile::options().read_only(true).open(&src)?;
+//!
+//! ndsfile.trim_with_name(&src.with_extension("trim.nds"))?;
+//! # Ok::<(), ndstrim::Error>(())
+//! ```
+
+#![warn(clippy::pedantic)]
+
+// Lets the test fixture shared with the benchmarks refer to the crate by name.
+#[cfg(test)]
+extern crate self as ndstrim;
+
+pub mod crc;
+#[cfg(test)]
+#[path = "../benches/fixture/mod.rs"]
+#[allow(dead_code, clippy::cast_possible_truncation)]
+mod fixture;
+pub mod nds;
+pub mod platform;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "sevenz")]
+pub mod sevenz;
+
+pub use nds::{Error, Handle, NdsFile, OpenOptions};
@@ -0,0 +1,211 @@
+//! Structs to keep persistent records of processed ROMs.
+
+#![warn(clippy::pedantic)]
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+/// A log file, to which one JSON object is appended per processed ROM.
+pub struct LogFile {
+    /// The path of the log file.
+    path: PathBuf,
+    /// The log file, opened for appending.
+    file: File,
+}
+
+impl LogFile {
+    /// Opens the log file at `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    /// Returns the path of the log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `entry` to the log file, flushing it right away.
+    pub fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.flush()
+    }
+}
+
+/// A directory holding one status file per processed ROM, for CI pipelines.
+pub struct StatusDir {
+    /// The path of the directory.
+    path: PathBuf,
+    /// The names of the status files written so far.
+    names: HashSet<String>,
+}
+
+impl StatusDir {
+    /// Opens the directory at `path`, creating it if needed.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        fs::create_dir_all(path)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            names: HashSet::new(),
+        })
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes `entry` to `<stem>.status`, returning the path of the status file.
+    ///
+    /// Sources sharing a stem get numbered status files, e.g. `foo.2.status`. Each file is written
+    /// through a temporary file, so it's never seen partially written.
+    pub fn write(&mut self, entry: &Entry) -> io::Result<PathBuf> {
+        let stem = entry
+            .source
+            .file_stem()
+            .map_or_else(|| "rom".into(), |s| s.to_string_lossy());
+        let mut name = format!("{stem}.status");
+        let mut n = 2;
+        while self.names.contains(&name) {
+            name = format!("{stem}.{n}.status");
+            n += 1;
+        }
+
+        let path = self.path.join(&name);
+        let tmp = self.path.join(format!(".{name}.tmp"));
+        fs::write(&tmp, entry.to_text())?;
+        fs::rename(&tmp, &path)?;
+        self.names.insert(name);
+
+        Ok(path)
+    }
+}
+
+/// The outcome of a whole run.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    /// Files that were trimmed, or would be.
+    pub trimmed: usize,
+    /// The bytes saved by trimming.
+    pub bytes_saved: u64,
+    /// Files that were skipped, by reason.
+    pub skipped: Skipped,
+    /// Files found not to be trimmed when checking.
+    pub untrimmed: usize,
+    /// Files that failed.
+    pub failed: usize,
+    /// Seconds spent processing files.
+    pub elapsed_secs: f64,
+    /// The files that failed and why.
+    pub failures: Vec<Failure<'a>>,
+}
+
+/// Counts of skipped files, by reason.
+#[derive(Serialize)]
+pub struct Skipped {
+    pub already_trimmed: usize,
+    pub permission_denied: usize,
+    pub excluded: usize,
+    pub existing: usize,
+    pub recent: usize,
+    pub read_only: usize,
+}
+
+/// A file that failed, and why.
+#[derive(Serialize)]
+pub struct Failure<'a> {
+    pub source: &'a Path,
+    pub reason: &'a str,
+}
+
+impl Report<'_> {
+    /// Writes `self` to `path` as a single JSON document, through a temporary file.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)
+    }
+}
+
+/// The outcome of processing a ROM.
+#[derive(Serialize)]
+pub struct Entry<'a> {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    source: &'a Path,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dest: Option<&'a Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trimmed_size: Option<u64>,
+    status: String,
+}
+
+impl<'a> Entry<'a> {
+    /// Describes `source`, which wasn't trimmed due to `status`.
+    pub fn new(source: &'a Path, status: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        Self {
+            timestamp,
+            source,
+            dest: None,
+            file_size: None,
+            trimmed_size: None,
+            status,
+        }
+    }
+
+    /// Describes `source`, which was trimmed from `file_size` to `trimmed_size` bytes into `dest`.
+    pub fn trimmed(
+        source: &'a Path,
+        dest: &'a Path,
+        file_size: u64,
+        trimmed_size: u64,
+        status: String,
+    ) -> Self {
+        Self {
+            dest: Some(dest),
+            file_size: Some(file_size),
+            trimmed_size: Some(trimmed_size),
+            ..Self::new(source, status)
+        }
+    }
+
+    /// Renders `self` as plain text, starting with the status.
+    fn to_text(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "status: {}", self.status);
+        let _ = writeln!(s, "timestamp: {}", self.timestamp);
+        let _ = writeln!(s, "source: {}", self.source.display());
+        if let Some(dest) = self.dest {
+            let _ = writeln!(s, "dest: {}", dest.display());
+        }
+        if let Some(file_size) = self.file_size {
+            let _ = writeln!(s, "file_size: {file_size}");
+        }
+        if let Some(trimmed_size) = self.trimmed_size {
+            let _ = writeln!(s, "trimmed_size: {trimmed_size}");
+        }
+        s
+    }
+}
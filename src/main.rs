@@ -2,50 +2,2432 @@
 
 #![warn(clippy::pedantic)]
 
+mod checksum;
 mod cli;
-mod crc;
-mod nds;
+mod config;
+#[cfg(test)]
+#[path = "../benches/fixture/mod.rs"]
+#[allow(dead_code, clippy::cast_possible_truncation)]
+mod fixture;
+mod logfile;
+mod naming;
+#[cfg(feature = "interactive")]
+mod picker;
+mod sidecar;
+mod term;
+mod vfs;
+mod walk;
 
-use clap::Parser;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use cli::Cli;
-use nds::NdsFile;
+#[cfg(feature = "remote")]
+use ndstrim::remote;
+#[cfg(feature = "sevenz")]
+use ndstrim::sevenz;
+use ndstrim::{crc, nds};
+
+use cli::{Cli, Dedupe, Hook, HookFailure, Overwrite, SummaryKey, Units};
+use logfile::{Entry, Failure, LogFile, Report, Skipped, StatusDir};
+use nds::{Error, Handle, NdsFile, OpenOptions, Rounding};
+use sidecar::Sidecar;
+use term::{eprintln, println, Tone};
+use vfs::{FileSystem, RealFs};
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = config::parse_args();
+    let fs = RealFs;
+    term::init(cli.color);
+
+    if cli.self_test {
+        process::exit(i32::from(!self_test()));
+    }
+
+    if cli.no_cert || cli.rounding == Rounding::Exact {
+        eprintln!(
+            "{}",
+            term::paint(
+                Tone::Warning,
+                "warning: RSA certificates will be discarded, Download Play may break"
+            )
+        );
+    }
+
+    let mut inputs = collect_files(&cli, &fs);
+
+    if !(cli.simulate
+        || cli.inplace
+        || cli.compare.is_some()
+        || cli.print_size
+        || cli.preview_layout
+        || cli.compare_tools
+        || cli.check
+        || cli.triage)
+    {
+        prepare_destinations(&cli, &fs, &inputs.roots, &inputs.files);
+    }
+
+    let filter = GamecodeFilter {
+        skip: cli.skip_gamecodes.as_deref().map(load_gamecodes),
+        only: cli.only_gamecodes.as_deref().map(load_gamecodes),
+    };
+
+    let known_sizes = KnownSizes {
+        by_crc: cli.size_map.as_deref().map(load_size_map),
+        by_serial: cli.db.as_deref().map(load_db),
+    };
+
+    let mut options = NdsFile::options();
+    options
+        .read_only(cli.simulate || !cli.inplace)
+        .lenient(cli.homebrew)
+        .accept_stale_crc(cli.accept_stale_crc || cli.fix_header_crc)
+        .preserve_cert(!cli.no_cert)
+        .rounding(cli.rounding)
+        .reserve_trailing(cli.reserve_trailing)
+        .tight(cli.tight)
+        .offset(cli.rom_offset)
+        .multi_rom(cli.multirom);
+
+    if cli.interactive {
+        inputs.files = pick_files(&options, inputs.files);
+    }
+    let files = &inputs.files;
+
+    if cli.triage {
+        process::exit(triage(&options, files));
+    }
+
+    handle_interrupts();
+
+    let mut summary = Summary::new(&cli);
+    let started = Instant::now();
+    let processed = process_all(
+        &cli,
+        &fs,
+        &filter,
+        &known_sizes,
+        &options,
+        &inputs,
+        &mut summary,
+    );
+
+    if let Some(path) = &cli.summary_json {
+        if let Err(e) = summary.report(started.elapsed()).write(path) {
+            report_error(path, &e.into());
+        }
+    }
+
+    if cli.check {
+        summary.print_untrimmed(cli.units);
+        exit_if_interrupted(processed, files.len());
+        if !summary.untrimmed.is_empty() || summary.failed > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(SummaryKey::Region) = cli.summary_by {
+        summary.print_by_region(cli.units);
+    }
+    if files.len() > 1 || processed < files.len() {
+        summary.print();
+    }
+    if summary.aborted {
+        eprintln!(
+            "{}",
+            term::paint(
+                Tone::Failure,
+                format!(
+                    "stopped after a failed post-hook, {processed} of {} files processed",
+                    files.len()
+                )
+            )
+        );
+        process::exit(1);
+    }
+    exit_if_interrupted(processed, files.len());
+}
+
+/// Set once the user interrupts the run, e.g. with Ctrl-C.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes interrupts stop the run once the file being processed is done, so that no file is left
+/// half-written. A second interrupt exits right away.
+fn handle_interrupts() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        eprintln!(
+            "{}",
+            term::paint(
+                Tone::Warning,
+                "interrupted, stopping after the current file, interrupt again to abort"
+            )
+        );
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "{}",
+            term::paint(
+                Tone::Warning,
+                format!("warning: interrupts will abort files midway: {e}")
+            )
+        );
+    }
+}
+
+/// Reports how far an interrupted run got and exits, if only `processed` of `total` files were
+/// processed.
+fn exit_if_interrupted(processed: usize, total: usize) {
+    if processed < total {
+        eprintln!(
+            "{}",
+            term::paint(
+                Tone::Warning,
+                format!("interrupted, {processed} of {total} files processed")
+            )
+        );
+        process::exit(130);
+    }
+}
+
+/// Processes each of the files in `inputs` according to `cli`, recording the outcomes in
+/// `summary`.
+///
+/// Returns the amount of files processed, which is less than the amount of files if the run was
+/// interrupted.
+fn process_all(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    filter: &GamecodeFilter,
+    known_sizes: &KnownSizes,
+    options: &OpenOptions,
+    inputs: &Inputs,
+    summary: &mut Summary,
+) -> usize {
+    let mut processed = 0;
+    thread::scope(|scope| {
+        let ahead = (cli.read_ahead > 0).then(|| read_ahead(scope, cli, options, &inputs.files));
+        for src in &inputs.files {
+            if INTERRUPTED.load(Ordering::SeqCst) || summary.aborted {
+                break;
+            }
+            processed += 1;
+
+            let dest = destination(cli, fs, &inputs.roots, src);
+            let opened = ahead.as_ref().and_then(|rx| rx.recv().ok()).flatten();
+            if is_too_recent(cli, fs, src) {
+                let outcome = FileOutcome::Skipped(Skip::TooRecent);
+                report_outcome(cli, src, &outcome, summary);
+                continue;
+            }
+            if cli.inplace && fs.is_block_device(src) {
+                let e = io::Error::new(
+                    ErrorKind::Unsupported,
+                    "block devices can't be trimmed in-place, trim into a copy instead",
+                );
+                report_outcome(cli, src, &FileOutcome::Failed(e.into()), summary);
+                continue;
+            }
+
+            let result = if is_url(src) {
+                open_remote(options, src)
+                    .and_then(|f| known_sizes.apply(src, f))
+                    .map(|f| process(cli, fs, filter, f, src, &dest, summary))
+            } else if is_7z(src) {
+                open_7z(options, src)
+                    .and_then(|f| known_sizes.apply(src, f))
+                    .map(|f| process(cli, fs, filter, f, src, &dest, summary))
+            } else if let Some(limit) = cli.scan_header {
+                open_scanning(options, src, limit)
+                    .and_then(|f| known_sizes.apply(src, f))
+                    .map(|f| process(cli, fs, filter, f, src, &dest, summary))
+            } else {
+                match opened.unwrap_or_else(|| options.open(src)) {
+                    Err(Error::Io(e))
+                        if e.kind() == ErrorKind::PermissionDenied && cli.inplace && !cli.simulate =>
+                    {
+                        report_read_only(cli, options, known_sizes, src, summary)
+                    }
+                    result => result
+                        .and_then(|f| known_sizes.apply(src, f))
+                        .map(|f| process(cli, fs, filter, f, src, &dest, summary)),
+                }
+            };
+            if let Err(e) = result {
+                let diagnose = cli.detect_patches
+                    && !is_url(src)
+                    && !is_7z(src)
+                    && matches!(e, Error::BadHeader | Error::StaleHeaderCrc { .. });
+                report_outcome(cli, src, &FileOutcome::Failed(e), summary);
+                if diagnose {
+                    report_header_checks(cli, src);
+                }
+            }
+        }
+    });
+
+    processed
+}
+
+/// What a file turned out to be when triaged.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Class {
+    Retail,
+    Homebrew,
+    AlreadyTrimmed,
+    Corrupt,
+    NotRom,
+    Unreadable,
+}
+
+impl Class {
+    /// Classifies a file from the outcome of opening it, returning the error that explains the
+    /// class, if any.
+    fn of<H: Handle>(opened: Result<NdsFile<H>, Error>) -> (Self, Option<Error>) {
+        let e = match opened {
+            Ok(f) if f.is_dsiware() => Error::UnsupportedFormat("DSiWare"),
+            Ok(f) if f.is_homebrew() || !f.logo_matches_canonical() => {
+                return (Class::Homebrew, None)
+            }
+            Ok(_) => return (Class::Retail, None),
+            Err(Error::AlreadyTrimmed) => return (Class::AlreadyTrimmed, None),
+            Err(e) => e,
+        };
+
+        let class = match e {
+            Error::BadHeader | Error::TooSmall { .. } | Error::UnsupportedFormat(_) => {
+                Class::NotRom
+            }
+            Error::Io(_) => Class::Unreadable,
+            _ => Class::Corrupt,
+        };
+        (class, Some(e))
+    }
+
+    /// Describes `self`.
+    fn name(self) -> &'static str {
+        match self {
+            Class::Retail => "retail ROM",
+            Class::Homebrew => "homebrew",
+            Class::AlreadyTrimmed => "already trimmed",
+            Class::Corrupt => "corrupt",
+            Class::NotRom => "not a ROM",
+            Class::Unreadable => "unreadable",
+        }
+    }
+
+    /// Returns the tone `self` is reported in.
+    fn tone(self) -> Tone {
+        match self {
+            Class::Retail | Class::Homebrew => Tone::Success,
+            Class::AlreadyTrimmed | Class::NotRom => Tone::Warning,
+            Class::Corrupt | Class::Unreadable => Tone::Failure,
+        }
+    }
+}
+
+/// Opens each of `files` read-only and reports what it is, without writing anything.
+///
+/// Returns the exit code, which is nonzero if any file is corrupt or couldn't be read.
+fn triage(options: &OpenOptions, files: &[PathBuf]) -> i32 {
+    let mut options = options.clone();
+    options.read_only(true).lenient(true);
+
+    let mut counts = BTreeMap::new();
+    for src in files {
+        let (class, reason) = if is_url(src) {
+            Class::of(open_remote(&options, src))
+        } else if is_7z(src) {
+            Class::of(open_7z(&options, src))
+        } else {
+            Class::of(options.open(src))
+        };
+        let shown = match reason {
+            Some(e) => format!("{}, {e}", class.name()),
+            None => class.name().to_owned(),
+        };
+        println!("'{}': {}", src.display(), term::paint(class.tone(), shown));
+        *counts.entry(class).or_insert(0) += 1;
+    }
+
+    for (class, count) in &counts {
+        eprintln!("{}", term::paint(class.tone(), format!("{}: {count}", class.name())));
+    }
+
+    i32::from(counts.contains_key(&Class::Corrupt) || counts.contains_key(&Class::Unreadable))
+}
+
+/// The files to process, along with the paths they were found from.
+struct Inputs {
+    /// The paths passed on the command line and listed by `--files-from`, with variables
+    /// expanded.
+    roots: Vec<PathBuf>,
+    /// The files to process, which are the files found under `roots` with `--recursive`.
+    files: Vec<PathBuf>,
+}
+
+/// Returns the files to process, including those listed by `--files-from`, expanding directories
+/// if `--recursive` is passed and dropping duplicates.
+fn collect_files(cli: &Cli, fs: &impl FileSystem) -> Inputs {
+    let mut roots: Vec<_> = cli
+        .files
+        .iter()
+        .map(|f| f.to_str().map_or_else(|| f.clone(), |s| PathBuf::from(&*expand(s))))
+        .collect();
+    if let Some(path) = &cli.files_from {
+        roots.extend(load_manifest(path));
+    }
+
+    let files = if cli.recursive {
+        let walk = walk::walk(fs, &roots, cli.max_depth, cli.follow_symlinks);
+        for (path, e) in walk.errors {
+            report_error(&path, &e.into());
+        }
+        walk.files
+    } else {
+        roots.clone()
+    };
+
+    // Trimmed copies left by earlier runs would only be reported as already trimmed. Files passed
+    // directly are processed regardless.
+    let files = if cli.inplace || cli.include_trimmed {
+        files
+    } else {
+        files
+            .into_iter()
+            .filter(|f| roots.contains(f) || !walk::is_trimmed_copy(f, &cli.extension))
+            .collect()
+    };
+
+    let (files, aliases) = walk::dedup_links(fs, files);
+    for (alias, path) in &aliases {
+        report_warning(alias, format!("skipped: same file as '{}'", path.display()));
+    }
+
+    Inputs { roots, files }
+}
+
+/// A local ROM opened ahead of time, or `None` for sources that are opened when processed.
+type Opened = Option<Result<NdsFile, Error>>;
+
+/// Opens the local ROMs among `files` on another thread, staying up to `--read-ahead` files ahead.
+///
+/// This overlaps the latency of reading headers, e.g. on network shares, with the processing of
+/// earlier files. The returned channel yields one item per file, in order.
+fn read_ahead<'scope>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    cli: &'scope Cli,
+    options: &'scope OpenOptions,
+    files: &'scope [PathBuf],
+) -> mpsc::Receiver<Opened> {
+    let (tx, rx) = mpsc::sync_channel(cli.read_ahead);
+    scope.spawn(move || {
+        for src in files {
+            let local = !(is_url(src) || is_7z(src) || cli.scan_header.is_some());
+            if tx.send(local.then(|| options.open(src))).is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+/// Lets the user choose which of `files` to trim, exiting if they cancel.
+#[cfg(feature = "interactive")]
+fn pick_files(options: &OpenOptions, files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let items = files
+        .into_iter()
+        .map(|path| {
+            let shown = path.display().to_string();
+            // Remote and archived ROMs are too slow to inspect upfront.
+            if is_url(&path) || is_7z(&path) {
+                return picker::Item {
+                    path,
+                    label: shown,
+                    selected: true,
+                };
+            }
+
+            let (label, selected) = match options.clone().read_only(true).open(&path) {
+                Ok(f) => (
+                    format!(
+                        "{shown}  {}  {} -> {} bytes",
+                        f.title(),
+                        f.file_size(),
+                        f.trimmed_size()
+                    ),
+                    true,
+                ),
+                Err(e) => (format!("{shown}  {e}"), false),
+            };
+            picker::Item {
+                path,
+                label,
+                selected,
+            }
+        })
+        .collect();
+
+    match picker::pick(items) {
+        Ok(Some(files)) => files,
+        Ok(None) => process::exit(0),
+        Err(e) => {
+            eprintln!("{}", term::paint(Tone::Failure, format!("error: {e}")));
+            process::exit(1);
+        }
+    }
+}
+
+/// Fails to let the user choose files, since interactive mode isn't enabled.
+#[cfg(not(feature = "interactive"))]
+fn pick_files(_options: &OpenOptions, _files: Vec<PathBuf>) -> Vec<PathBuf> {
+    eprintln!(
+        "{}",
+        term::paint(
+            Tone::Failure,
+            "error: interactive mode isn't supported by this build"
+        )
+    );
+    process::exit(1);
+}
+
+/// Processes `ndsfile`, opened from `src`, trims it into `dest` according to `cli`, and reports
+/// the outcome.
+fn process<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    filter: &GamecodeFilter,
+    mut ndsfile: NdsFile<H>,
+    src: &Path,
+    dest: &Path,
+    summary: &mut Summary,
+) {
+    let outcome = process_file(cli, fs, filter, &mut ndsfile, src, dest, &mut summary.copies);
+    report_outcome(cli, src, &outcome, summary);
+
+    let trimmed = matches!(outcome, FileOutcome::Trimmed(_));
+    if trimmed {
+        finish_trim(cli, &mut ndsfile, src, dest);
+    }
+
+    if let (Some(hook), FileOutcome::Trimmed(rom)) = (&cli.post_hook, &outcome) {
+        if !rom.simulated {
+            if let Err(e) = run_hook(hook, src, &rom.dest, rom.file_size - rom.trimmed_size) {
+                report_error(src, &e.into());
+                summary.aborted = cli.hook_failure == HookFailure::Abort;
+            }
+        }
+    }
+
+    if let (true, Some(template)) = (trimmed, &cli.rename) {
+        rename(cli, fs, template, ndsfile, src);
+        return;
+    }
+
+    if cli.multirom {
+        trim_following(cli, fs, filter, &ndsfile, src, dest, summary);
+    }
+}
+
+/// The outcome of processing a ROM, before it's reported.
+enum FileOutcome {
+    /// The ROM was trimmed, or would be when simulating.
+    Trimmed(TrimmedRom),
+    /// The ROM could be trimmed by `saved` bytes, as found when checking.
+    Untrimmed { saved: u64 },
+    /// Only the trimmed size of the ROM was requested.
+    Measured { trimmed_size: u64 },
+    /// The ROM was inspected without anything to report, e.g. when comparing.
+    Inspected,
+    /// The ROM was skipped.
+    Skipped(Skip),
+    /// The ROM couldn't be processed.
+    Failed(Error),
+}
+
+/// A ROM that was trimmed, or would be when simulating.
+struct TrimmedRom {
+    /// Where the ROM was trimmed into.
+    dest: PathBuf,
+    /// The region derived from the game code.
+    region: &'static str,
+    /// The size of the file before trimming.
+    file_size: u64,
+    /// The size of the file after trimming.
+    trimmed_size: u64,
+    /// Whether trimming was only simulated.
+    simulated: bool,
+}
+
+/// The reasons for skipping a ROM.
+enum Skip {
+    /// The file was modified more recently than `--min-age`.
+    TooRecent,
+    /// The game code is excluded by `--skip-gamecodes` or `--only-gamecodes`.
+    Excluded { gamecode: String },
+    /// The trimmed copy already exists at `dest`, and mustn't be overwritten.
+    Exists { dest: PathBuf },
+    /// The ROM can only be opened read-only, so it can't be trimmed in-place.
+    ReadOnly { file_size: u64, trimmed_size: u64 },
+}
+
+/// Processes `ndsfile`, opened from `src`, and trims it into `dest` according to `cli`.
+///
+/// Problems that aren't fatal are reported right away, the outcome is left to the caller.
+fn process_file<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    filter: &GamecodeFilter,
+    ndsfile: &mut NdsFile<H>,
+    src: &Path,
+    dest: &Path,
+    copies: &mut Copies,
+) -> FileOutcome {
+    ndsfile.set_buffer_size(cli.buffer_size);
+    ndsfile.set_sparse(cli.sparse);
+
+    let gamecode = ndsfile.gamecode();
+    if filter.excludes(&gamecode) {
+        return FileOutcome::Skipped(Skip::Excluded { gamecode });
+    }
+
+    if let Err(e) = inspect(cli, ndsfile, src) {
+        return FileOutcome::Failed(e);
+    }
+
+    if cli.verbose {
+        print_details(ndsfile, src);
+    }
+
+    if cli.scan_padding {
+        scan_padding(ndsfile, src);
+    }
+
+    if cli.preview_layout {
+        return match print_layout(ndsfile, src) {
+            Ok(()) => FileOutcome::Inspected,
+            Err(e) => FileOutcome::Failed(e),
+        };
+    }
+
+    if cli.capacity_report {
+        print_capacity(ndsfile, src, cli.units);
+        return FileOutcome::Inspected;
+    }
+
+    if cli.compare_tools {
+        print_tool_comparison(ndsfile, src, cli.units);
+        return FileOutcome::Inspected;
+    }
+
+    if cli.print_size {
+        return FileOutcome::Measured {
+            trimmed_size: ndsfile.trimmed_size(),
+        };
+    }
+
+    if cli.check {
+        let saved = ndsfile.file_size() - ndsfile.trimmed_size();
+        if saved > cli.min_savings {
+            return FileOutcome::Untrimmed { saved };
+        }
+        return FileOutcome::Inspected;
+    }
+
+    if let Some(other) = &cli.compare {
+        compare(ndsfile, src, other);
+        return FileOutcome::Inspected;
+    }
+
+    if cli.require_reversible && !cli.force {
+        let result = ndsfile.scan_padding().and_then(|p| match p.first_other {
+            Some(offset) => Err(Error::Irreversible { offset }),
+            None => Ok(()),
+        });
+        if let Err(e) = result {
+            return FileOutcome::Failed(e);
+        }
+    }
+
+    if let (Some(dir), false) = (&cli.save_tail, cli.simulate) {
+        if let Err(e) = save_tail(cli, fs, ndsfile, src, dir) {
+            return FileOutcome::Failed(e);
+        }
+    }
+
+    trim(cli, fs, ndsfile, dest, copies)
+}
+
+/// Saves the data that trimming `ndsfile`, opened from `src`, removes to a `.tail` file in `dir`,
+/// and reports whether it's all padding.
+fn save_tail<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    ndsfile: &mut NdsFile<H>,
+    src: &Path,
+    dir: &Path,
+) -> Result<(), Error> {
+    let mut name = src.file_stem().unwrap_or_default().to_owned();
+    name.push(".tail");
+    let path = dir.join(name);
+
+    fs.create_dir_all(dir)?;
+    let mut file = BufWriter::new(File::create(&path)?);
+    let padding = ndsfile.copy_tail(&mut file)?;
+    file.flush()?;
+
+    let contents = if padding.other == 0 {
+        "all padding".to_owned()
+    } else {
+        format!("{} not padding", cli.units.amount(padding.other))
+    };
+    println!(
+        "'{}': saved {} of removed data to '{}', {contents}",
+        src.display(),
+        cli.units.amount(ndsfile.file_size() - ndsfile.trimmed_size()),
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// Reports `outcome`, the outcome of processing `src`, and records it in `summary`.
+fn report_outcome(cli: &Cli, src: &Path, outcome: &FileOutcome, summary: &mut Summary) {
+    match outcome {
+        FileOutcome::Trimmed(rom) => {
+            summary.record_trimmed(src, rom);
+            println!(
+                "'{}': {}",
+                rom.dest.display(),
+                term::paint(
+                    Tone::Success,
+                    format!(
+                        "size reduced from {} to {}",
+                        cli.units.size(rom.file_size),
+                        cli.units.size(rom.trimmed_size)
+                    )
+                )
+            );
+        }
+        FileOutcome::Untrimmed { saved } => summary.record_untrimmed(src, *saved),
+        FileOutcome::Measured { trimmed_size } => println!("{trimmed_size}"),
+        FileOutcome::Inspected => {}
+        FileOutcome::Skipped(Skip::TooRecent) => {
+            report_warning(src, "skipped: too recent");
+            summary.record_recent(src);
+        }
+        FileOutcome::Skipped(Skip::Excluded { gamecode }) => {
+            report_warning(src, format!("skipped: game code {gamecode} excluded"));
+            summary.record_excluded(src);
+        }
+        FileOutcome::Skipped(Skip::Exists { dest }) => {
+            report_warning(src, format!("skipped: '{}' exists", dest.display()));
+            summary.record_existing(src);
+        }
+        FileOutcome::Skipped(Skip::ReadOnly {
+            file_size,
+            trimmed_size,
+        }) => {
+            report_warning(
+                src,
+                format!(
+                    "read-only: would trim from {} to {}, run with write access to apply",
+                    cli.units.size(*file_size),
+                    cli.units.size(*trimmed_size)
+                ),
+            );
+            summary.record_read_only(src);
+        }
+        // Already trimmed ROMs are the expected outcome of a check.
+        FileOutcome::Failed(e @ Error::AlreadyTrimmed) if cli.check || cli.hide_already_trimmed => {
+            summary.record(src, e);
+        }
+        FileOutcome::Failed(e) => {
+            report_error(src, e);
+            summary.record(src, e);
+        }
+    }
+}
+
+/// Checks `ndsfile`, opened from `src`, for problems, and reports those that aren't fatal
+/// according to `cli`.
+fn inspect<H: Handle>(cli: &Cli, ndsfile: &mut NdsFile<H>, src: &Path) -> Result<(), Error> {
+    if ndsfile.is_homebrew() {
+        report_warning(src, "note: homebrew build");
+    }
+
+    check_header_crc(cli, ndsfile, src)?;
+
+    if ndsfile.is_dsiware() {
+        return Err(Error::UnsupportedFormat("DSiWare"));
+    }
+
+    if cli.strict_logo && !ndsfile.logo_matches_canonical() {
+        return Err(Error::LogoMismatch);
+    }
+
+    if let Some(w) = ndsfile.size_warning() {
+        if cli.strict {
+            return Err(Error::InconsistentSize(w));
+        }
+        report_warning(src, format!("warning: {w}"));
+    }
+
+    // Homebrew sizes are never aligned to cartridge sizes.
+    if !(ndsfile.is_homebrew() || ndsfile.is_aligned(cli.alignment)) {
+        let e = Error::Unaligned {
+            size: ndsfile.used_size(),
+            alignment: cli.alignment,
+        };
+        if cli.strict {
+            return Err(e);
+        }
+        report_warning(src, format!("warning: {e}"));
+    }
+
+    if cli.check_naming {
+        for m in naming::check(src, ndsfile.region(), &ndsfile.title()) {
+            // Internal titles are too abbreviated for their mismatches to be more than a hint.
+            if cli.strict && matches!(m, naming::Mismatch::Region { .. }) {
+                return Err(Error::Misnamed(m.to_string()));
+            }
+            report_warning(src, format!("warning: misnamed, {m}"));
+        }
+    }
+
+    if let Some(note) = fat_end_note(ndsfile) {
+        report_warning(src, note);
+    }
+
+    if ndsfile.overlays_end() > ndsfile.used_size() {
+        report_warning(
+            src,
+            format!(
+                "last overlay ends at {}, header declares {}, keeping it",
+                ndsfile.overlays_end(),
+                ndsfile.used_size()
+            ),
+        );
+    }
+
+    check_over_padding(cli, ndsfile, src)
+}
+
+/// Describes how the end of the last file in `ndsfile`'s file allocation table differs from the
+/// size declared in its header, if it was opened with `--tight` and they differ.
+fn fat_end_note<H: Handle>(ndsfile: &NdsFile<H>) -> Option<String> {
+    let fat_end = ndsfile.fat_end().filter(|&end| end != 0 && end != ndsfile.used_size())?;
+    Some(format!(
+        "note: last file ends at {}, header declares {}",
+        fat_end,
+        ndsfile.used_size()
+    ))
+}
+
+/// Reports a stale header CRC in `ndsfile`, fixing it if requested by `cli`.
+fn check_header_crc<H: Handle>(
+    cli: &Cli,
+    ndsfile: &mut NdsFile<H>,
+    src: &Path,
+) -> Result<(), Error> {
+    if ndsfile.header_crc_valid() {
+        return Ok(());
+    }
+
+    if cli.fix_header_crc && !cli.simulate {
+        ndsfile.fix_header_crc()?;
+        report_warning(src, "note: stale header CRC fixed");
+    } else {
+        report_warning(src, "warning: stale header CRC");
+    }
+
+    Ok(())
+}
+
+/// Reports zero padding past the cartridge capacity of `ndsfile`, and only trims that if
+/// requested by `cli`.
+fn check_over_padding<H: Handle>(
+    cli: &Cli,
+    ndsfile: &mut NdsFile<H>,
+    src: &Path,
+) -> Result<(), Error> {
+    match ndsfile.over_padding()? {
+        Some(extra) => {
+            report_warning(
+                src,
+                format!(
+                    "note: {} of zero padding past the cartridge capacity",
+                    cli.units.amount(extra)
+                ),
+            );
+            if cli.strip_over_padding {
+                ndsfile.trim_to(ndsfile.device_capacity())?;
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Prints details about `ndsfile`, opened from `src`, that don't show in the usual output.
+fn print_details<H: Handle>(ndsfile: &mut NdsFile<H>, src: &Path) {
+    if let Some(id) = ndsfile.title_id() {
+        println!("'{}': title ID {id:016X}", src.display());
+    }
+
+    println!(
+        "'{}': trim size based on the {}",
+        src.display(),
+        ndsfile.trim_reason()
+    );
+
+    let used_size = ndsfile.used_size();
+    match ndsfile.cert_range() {
+        Some(cert) if cert.start == used_size => {
+            println!("'{}': RSA certificate at {:#x}", src.display(), cert.start);
+        }
+        Some(cert) => println!(
+            "'{}': RSA certificate at {:#x}, {} bytes past the ROM data",
+            src.display(),
+            cert.start,
+            cert.start - used_size
+        ),
+        None => println!("'{}': no RSA certificate", src.display()),
+    }
+
+    match ndsfile.banner_crc_valid() {
+        Ok(Some(true)) => println!("'{}': banner CRC valid", src.display()),
+        Ok(Some(false)) => report_warning(src, "warning: banner CRC mismatch"),
+        Ok(None) => println!("'{}': no banner CRC to check", src.display()),
+        Err(e) => return report_error(src, &e),
+    }
+
+    // The English title is the second one.
+    match ndsfile.banner_titles() {
+        Ok(titles) => {
+            if let Some(title) = titles.get(1) {
+                let title = title.lines().collect::<Vec<_>>().join(" / ");
+                println!("'{}': banner title '{title}'", src.display());
+            }
+        }
+        Err(e) => report_error(src, &e),
+    }
+}
+
+/// Reports the byte values in the data that trimming `ndsfile` would remove.
+fn scan_padding<H: Handle>(ndsfile: &mut NdsFile<H>, src: &Path) {
+    match ndsfile.scan_padding() {
+        Ok(p) => {
+            println!(
+                "'{}': removed data has {} 0xFF, {} 0x00 and {} other bytes",
+                src.display(),
+                p.ff,
+                p.zero,
+                p.other
+            );
+            if p.other > 0 {
+                report_warning(
+                    src,
+                    "warning: removed data isn't all padding, the header size may be wrong",
+                );
+            }
+        }
+        Err(e) => report_error(src, &e),
+    }
+}
+
+/// Prints the sections of `ndsfile`, opened from `src`, marking those that trimming discards.
+fn print_layout<H: Handle>(ndsfile: &mut NdsFile<H>, src: &Path) -> Result<(), Error> {
+    let mut sections = ndsfile.layout()?;
+    let trimmed_size = ndsfile.trimmed_size();
+    sections.push(nds::Section {
+        name: "padding",
+        range: trimmed_size..ndsfile.file_size(),
+    });
+
+    println!("'{}':", src.display());
+    println!("  {:<10}  {:<10}  section", "offset", "size");
+    for section in sections {
+        let (start, end) = (section.range.start, section.range.end);
+        let mark = if start >= trimmed_size {
+            term::paint(Tone::Warning, "  (discarded)")
+        } else if end > trimmed_size {
+            term::paint(Tone::Warning, "  (partly discarded)")
+        } else {
+            String::new()
+        };
+        println!(
+            "  {start:#010x}  {:#010x}  {}{mark}",
+            end - start,
+            section.name
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the used size, cartridge capacity and file size of `ndsfile`, opened from `src`, in
+/// `units`, warning if the file is neither at full capacity nor trimmed.
+fn print_capacity<H: Handle>(ndsfile: &NdsFile<H>, src: &Path, units: Units) {
+    let (file_size, full_size) = (ndsfile.file_size(), ndsfile.full_size());
+    println!(
+        "'{}': used {}, capacity {}, file {}",
+        src.display(),
+        units.size(ndsfile.used_size()),
+        units.size(ndsfile.device_capacity()),
+        units.size(file_size)
+    );
+
+    // Already trimmed ROMs fail to open, so the file is larger than the trimmed size here.
+    if file_size < full_size {
+        report_warning(
+            src,
+            "warning: neither at full capacity nor trimmed, this may be a partial dump",
+        );
+    }
+}
+
+/// Prints the trimmed size of `ndsfile`, opened from `src`, next to the size most other trimmers
+/// would produce, explaining the difference.
+///
+/// Such trimmers cut ROMs right at the size declared in the header, discarding the RSA
+/// certificate and anything else `ndstrim` keeps past it.
+fn print_tool_comparison<H: Handle>(ndsfile: &NdsFile<H>, src: &Path, units: Units) {
+    let (trimmed_size, declared) = (ndsfile.trimmed_size(), ndsfile.used_size());
+    let difference = if trimmed_size > declared {
+        format!(
+            "{} bytes more, as the trim size is based on the {}",
+            trimmed_size - declared,
+            ndsfile.trim_reason()
+        )
+    } else {
+        "no difference".to_owned()
+    };
+    println!(
+        "'{}': ndstrim {}, header size only {}, {difference}",
+        src.display(),
+        units.size(trimmed_size),
+        units.size(declared)
+    );
+}
+
+/// Reports which checks the header of `src` fails, and whether it looks patched or corrupt.
+fn report_header_checks(cli: &Cli, src: &Path) {
+    let checks = File::open(src).map_err(Error::from).and_then(|mut f| {
+        f.seek(SeekFrom::Start(cli.rom_offset))?;
+        nds::check_header(&mut f)
+    });
+    let checks = match checks {
+        Ok(checks) => checks,
+        Err(e) => return report_error(src, &e),
+    };
+
+    let status = |valid| if valid { "valid" } else { "invalid" };
+    let verdict = if !checks.sizes_plausible {
+        "sizes implausible, likely a corrupt dump or not a ROM"
+    } else if !checks.logo_crc_valid && checks.header_crc_valid {
+        "likely patched, the Nintendo logo was replaced, e.g. by an intro"
+    } else if checks.logo_crc_valid && !checks.header_crc_valid {
+        "likely patched, the header was edited without updating its CRC"
+    } else if !checks.logo_crc_valid {
+        "likely a corrupt dump, both CRCs fail"
+    } else if !checks.logo_canonical {
+        "likely patched, the Nintendo logo was forged to pass its CRC"
+    } else {
+        "every check passes"
+    };
+    report_warning(
+        src,
+        format!(
+            "note: logo CRC {}, header CRC {}, {verdict}",
+            status(checks.logo_crc_valid),
+            status(checks.header_crc_valid)
+        ),
+    );
+}
+
+/// Reports what trimming `src` in-place would do, as it couldn't be opened for writing.
+fn report_read_only(
+    cli: &Cli,
+    options: &OpenOptions,
+    known_sizes: &KnownSizes,
+    src: &Path,
+    summary: &mut Summary,
+) -> Result<(), Error> {
+    let ndsfile = options.clone().read_only(true).open(src)?;
+    let ndsfile = known_sizes.apply(src, ndsfile)?;
+    let outcome = FileOutcome::Skipped(Skip::ReadOnly {
+        file_size: ndsfile.file_size(),
+        trimmed_size: ndsfile.trimmed_size(),
+    });
+    report_outcome(cli, src, &outcome, summary);
+    Ok(())
+}
+
+/// Opens the ROM in `src` at the offset of the first header within `limit` bytes.
+fn open_scanning(options: &OpenOptions, src: &Path, limit: u64) -> Result<NdsFile, Error> {
+    let offset = nds::find_header(&mut File::open(src)?, limit)?.ok_or(Error::BadHeader)?;
+    if offset != 0 {
+        report_warning(src, format!("header found at offset {offset:#x}"));
+    }
+
+    options.clone().offset(offset).open(src)
+}
+
+/// Opens the ROM at the URL in `src`.
+#[cfg(feature = "remote")]
+fn open_remote(options: &OpenOptions, src: &Path) -> Result<NdsFile<remote::HttpReader>, Error> {
+    let url = src.to_string_lossy();
+    options.open_handle(remote::HttpReader::open(&url)?)
+}
+
+/// Fails to open the ROM at the URL in `src`, since remote support isn't enabled.
+#[cfg(not(feature = "remote"))]
+fn open_remote(_options: &OpenOptions, _src: &Path) -> Result<NdsFile, Error> {
+    Err(Error::Io(io::Error::new(
+        ErrorKind::Unsupported,
+        "remote files aren't supported by this build",
+    )))
+}
+
+/// Checks whether `path` is actually an HTTP(S) URL.
+fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://"))
+}
+
+/// Opens the ROM in the 7z archive at `src`.
+#[cfg(feature = "sevenz")]
+fn open_7z(options: &OpenOptions, src: &Path) -> Result<NdsFile<sevenz::ArchivedRom>, Error> {
+    options.open_handle(sevenz::ArchivedRom::open(src)?)
+}
+
+/// Fails to open the ROM in the 7z archive at `src`, since 7z support isn't enabled.
+#[cfg(not(feature = "sevenz"))]
+fn open_7z(_options: &OpenOptions, _src: &Path) -> Result<NdsFile, Error> {
+    Err(Error::Io(io::Error::new(
+        ErrorKind::Unsupported,
+        "7z archives aren't supported by this build",
+    )))
+}
+
+/// Checks whether `path` is a 7z archive, judging by its extension.
+fn is_7z(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("7z"))
+}
+
+/// Checks the checksum implementations against known vectors, returning whether all match.
+fn self_test() -> bool {
+    let crc16 = [
+        ("empty", &[][..], 0xffff),
+        ("check string", b"123456789", 0x4b37),
+        ("Nintendo logo", &nds::NINTENDO_LOGO, 0xcf56),
+        ("erased header", &[0xff; 0x15e], 0x790b),
+        ("zeroed header", &[0x00; 0x15e], 0x1bcc),
+    ];
+    let crc32 = [
+        ("empty", &[][..], 0),
+        ("check string", b"123456789", 0xcbf4_3926),
+    ];
+
+    let mut ok = true;
+    for (name, data, expected) in crc16 {
+        let actual = crc::checksum(data);
+        if actual != expected {
+            eprintln!(
+                "{}",
+                term::paint(
+                    Tone::Failure,
+                    format!("CRC-16 of {name}: expected {expected:04X}, got {actual:04X}")
+                )
+            );
+            ok = false;
+        }
+    }
+    for (name, data, expected) in crc32 {
+        let mut crc = crc::Crc32::new();
+        crc.update(data);
+        let actual = crc.finalize();
+        if actual != expected {
+            eprintln!(
+                "{}",
+                term::paint(
+                    Tone::Failure,
+                    format!("CRC-32 of {name}: expected {expected:08X}, got {actual:08X}")
+                )
+            );
+            ok = false;
+        }
+    }
+
+    if ok {
+        println!("{}", term::paint(Tone::Success, "all checksums match"));
+    }
+    ok
+}
+
+/// Known-good trimmed sizes, keyed by the CRC-32 of the header.
+type SizeMap = HashMap<u32, u64>;
+
+/// Canonical trimmed sizes from a ROM database, keyed by [`NdsFile::serial`].
+type SizeDb = HashMap<String, u64>;
+
+/// Trimmed sizes that override the ones computed from the header.
+struct KnownSizes {
+    /// The size map passed with `--size-map`, if any.
+    by_crc: Option<SizeMap>,
+    /// The database passed with `--db`, if any.
+    by_serial: Option<SizeDb>,
+}
+
+impl KnownSizes {
+    /// Trims `ndsfile` to the size listed for it, if any.
+    ///
+    /// The size map takes precedence over the database, as header CRCs tell apart dumps that
+    /// share a serial.
+    fn apply<H: Handle>(&self, src: &Path, mut ndsfile: NdsFile<H>) -> Result<NdsFile<H>, Error> {
+        if let Some(size_map) = &self.by_crc {
+            if let Some(&size) = size_map.get(&ndsfile.header_crc32()?) {
+                ndsfile.trim_to(size)?;
+                report_warning(src, format!("trimming to {size} bytes from the size map"));
+                return Ok(ndsfile);
+            }
+        }
+
+        if let Some(db) = &self.by_serial {
+            let serial = ndsfile.serial();
+            match db.get(&serial) {
+                Some(&size) => {
+                    ndsfile.trim_to(size)?;
+                    report_warning(src, format!("trimming to {size} bytes from the database"));
+                }
+                None => report_warning(
+                    src,
+                    format!("note: {serial} isn't in the database, using the computed size"),
+                ),
+            }
+        }
+
+        Ok(ndsfile)
+    }
+}
+
+/// Checks whether the local file at `src` was modified more recently than `--min-age`.
+///
+/// Files whose modification time can't be read aren't considered recent, so that opening them
+/// reports the error.
+fn is_too_recent(cli: &Cli, fs: &impl FileSystem, src: &Path) -> bool {
+    let Some(min_age) = cli.min_age else {
+        return false;
+    };
+    if is_url(src) {
+        return false;
+    }
+
+    fs.modified(src).is_ok_and(|modified| {
+        // Modification times in the future are as recent as it gets.
+        SystemTime::now()
+            .duration_since(modified)
+            .map_or(true, |age| age < min_age)
+    })
+}
+
+/// Sets of game codes to skip or exclusively process.
+struct GamecodeFilter {
+    /// Game codes to skip.
+    skip: Option<HashSet<String>>,
+    /// Game codes to exclusively process.
+    only: Option<HashSet<String>>,
+}
+
+impl GamecodeFilter {
+    /// Checks whether ROMs with `gamecode` should be skipped.
+    fn excludes(&self, gamecode: &str) -> bool {
+        let skipped = self.skip.as_ref().is_some_and(|c| c.contains(gamecode));
+        let listed = self.only.as_ref().is_none_or(|c| c.contains(gamecode));
+        skipped || !listed
+    }
+}
+
+/// Counts of processed files, grouped by outcome.
+#[derive(Default)]
+struct Summary {
+    /// Files that were trimmed, or would be, and the bytes saved, grouped by region.
+    trimmed_by_region: BTreeMap<&'static str, (usize, u64)>,
+    /// Files that were already trimmed.
+    already_trimmed: usize,
+    /// Files that couldn't be accessed due to insufficient permissions.
+    permission_denied: usize,
+    /// Files excluded by their game code.
+    excluded: usize,
+    /// Files skipped because their trimmed copy already exists.
+    existing: usize,
+    /// Files skipped because they were modified more recently than `--min-age`.
+    recent: usize,
+    /// Files skipped because they could only be opened read-only when trimming in-place.
+    read_only: usize,
+    /// Files found not to be trimmed when checking, and the bytes that could be saved.
+    untrimmed: Vec<(PathBuf, u64)>,
+    /// Files that failed for any other reason.
+    failed: usize,
+    /// The files that failed and why.
+    failures: Vec<(PathBuf, String)>,
+    /// The trimmed copies written so far, to link duplicates to.
+    copies: Copies,
+    /// Whether the run was stopped early, after a post-hook failed with `--hook-failure abort`.
+    aborted: bool,
+    /// The log file every outcome is appended to, if any.
+    log: Option<LogFile>,
+    /// The directory every outcome is written to, if any.
+    status_dir: Option<StatusDir>,
+}
+
+impl Summary {
+    /// Creates an empty summary, opening the log file and status directory in `cli`, if any.
+    ///
+    /// Exits on failure.
+    fn new(cli: &Cli) -> Self {
+        let mut summary = Self::default();
+        if let Some(path) = &cli.log_file {
+            match LogFile::open(path) {
+                Ok(log) => summary.log = Some(log),
+                Err(e) => {
+                    report_error(path, &e.into());
+                    process::exit(1);
+                }
+            }
+        }
+        if let Some(path) = &cli.status_dir {
+            match StatusDir::create(path) {
+                Ok(dir) => summary.status_dir = Some(dir),
+                Err(e) => {
+                    report_error(path, &e.into());
+                    process::exit(1);
+                }
+            }
+        }
+
+        summary
+    }
+
+    /// Records `src`, which was trimmed as described by `rom`.
+    fn record_trimmed(&mut self, src: &Path, rom: &TrimmedRom) {
+        let (files, bytes) = self.trimmed_by_region.entry(rom.region).or_default();
+        *files += 1;
+        *bytes += rom.file_size - rom.trimmed_size;
+
+        let status = if rom.simulated { "simulated" } else { "trimmed" };
+        self.log(&Entry::trimmed(
+            src,
+            &rom.dest,
+            rom.file_size,
+            rom.trimmed_size,
+            status.to_owned(),
+        ));
+    }
 
-    for src in &cli.files {
-        let dest = if cli.inplace {
-            src.clone()
+    /// Records `src`, which was skipped because its trimmed copy already exists.
+    fn record_existing(&mut self, src: &Path) {
+        self.existing += 1;
+        self.log(&Entry::new(src, "skipped: destination exists".to_owned()));
+    }
+
+    /// Records `src`, which was skipped because it was modified too recently.
+    fn record_recent(&mut self, src: &Path) {
+        self.recent += 1;
+        self.log(&Entry::new(src, "skipped: too recent".to_owned()));
+    }
+
+    /// Records `src`, which was skipped because it could only be opened read-only.
+    fn record_read_only(&mut self, src: &Path) {
+        self.read_only += 1;
+        self.log(&Entry::new(src, "skipped: read-only".to_owned()));
+    }
+
+    /// Records `src`, which was excluded by its game code.
+    fn record_excluded(&mut self, src: &Path) {
+        self.excluded += 1;
+        self.log(&Entry::new(src, "skipped: game code excluded".to_owned()));
+    }
+
+    /// Records `src`, which could be trimmed by `saved` bytes.
+    fn record_untrimmed(&mut self, src: &Path, saved: u64) {
+        self.untrimmed.push((src.to_path_buf(), saved));
+        self.log(&Entry::new(src, format!("untrimmed: {saved} bytes to trim")));
+    }
+
+    /// Prints the files found not to be trimmed when checking, with sizes in `units`.
+    fn print_untrimmed(&self, units: Units) {
+        for (src, saved) in &self.untrimmed {
+            println!(
+                "'{}': {}",
+                src.display(),
+                term::paint(
+                    Tone::Failure,
+                    format!("not trimmed, {} to trim", units.amount(*saved))
+                )
+            );
+        }
+        let count = self.untrimmed.len();
+        let files = if count == 1 { "file" } else { "files" };
+        eprintln!(
+            "{}",
+            term::paint(
+                if count == 0 { Tone::Success } else { Tone::Failure },
+                format!("{count} {files} not trimmed")
+            )
+        );
+    }
+
+    /// Prints the amount of trimmed files and bytes saved in each region, in `units`.
+    fn print_by_region(&self, units: Units) {
+        for (region, (files, bytes)) in &self.trimmed_by_region {
+            let noun = if *files == 1 { "file" } else { "files" };
+            println!("{region}: {files} {noun}, {} saved", units.amount(*bytes));
+        }
+    }
+
+    /// Records `src`, which couldn't be trimmed due to `e`.
+    fn record(&mut self, src: &Path, e: &Error) {
+        let status = match e {
+            Error::AlreadyTrimmed => {
+                self.already_trimmed += 1;
+                format!("skipped: {e}")
+            }
+            Error::Io(io) if io.kind() == ErrorKind::PermissionDenied => {
+                self.permission_denied += 1;
+                format!("skipped: {e}")
+            }
+            _ => {
+                self.failed += 1;
+                self.failures.push((src.to_path_buf(), e.to_string()));
+                format!("failed: {e}")
+            }
+        };
+        self.log(&Entry::new(src, status));
+    }
+
+    /// Appends `entry` to the log file, and writes it to the status directory, if any.
+    fn log(&mut self, entry: &Entry) {
+        if let Some(log) = &mut self.log {
+            if let Err(e) = log.append(entry) {
+                report_warning(log.path(), format!("warning: couldn't write log: {e}"));
+            }
+        }
+        if let Some(dir) = &mut self.status_dir {
+            if let Err(e) = dir.write(entry) {
+                report_warning(
+                    dir.path(),
+                    format!("warning: couldn't write status file: {e}"),
+                );
+            }
+        }
+    }
+
+    /// Returns a report of the run, which took `elapsed` so far.
+    fn report(&self, elapsed: Duration) -> Report<'_> {
+        Report {
+            trimmed: self.trimmed_by_region.values().map(|(files, _)| files).sum(),
+            bytes_saved: self.trimmed_by_region.values().map(|(_, bytes)| bytes).sum(),
+            skipped: Skipped {
+                already_trimmed: self.already_trimmed,
+                permission_denied: self.permission_denied,
+                excluded: self.excluded,
+                existing: self.existing,
+                recent: self.recent,
+                read_only: self.read_only,
+            },
+            untrimmed: self.untrimmed.len(),
+            failed: self.failed,
+            elapsed_secs: elapsed.as_secs_f64(),
+            failures: self
+                .failures
+                .iter()
+                .map(|(source, reason)| Failure { source, reason })
+                .collect(),
+        }
+    }
+
+    /// Prints the counts that aren't zero.
+    fn print(&self) {
+        let lines = [
+            (
+                self.already_trimmed,
+                "skipped: already trimmed",
+                Tone::Warning,
+            ),
+            (
+                self.permission_denied,
+                "skipped: permission denied",
+                Tone::Warning,
+            ),
+            (
+                self.excluded,
+                "skipped: excluded by game code",
+                Tone::Warning,
+            ),
+            (
+                self.existing,
+                "skipped: destination exists",
+                Tone::Warning,
+            ),
+            (self.recent, "skipped: too recent", Tone::Warning),
+            (self.read_only, "skipped: read-only", Tone::Warning),
+            (self.failed, "failed", Tone::Failure),
+        ];
+        for (count, what, tone) in lines {
+            if count > 0 {
+                let files = if count == 1 { "file" } else { "files" };
+                eprintln!("{}", term::paint(tone, format!("{count} {files} {what}")));
+            }
+        }
+    }
+}
+
+/// Trims `ndsfile` into `dest` according to `cli`, recording the copy in `copies`.
+fn trim<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    ndsfile: &mut NdsFile<H>,
+    dest: &Path,
+    copies: &mut Copies,
+) -> FileOutcome {
+    if !cli.inplace {
+        match apply_overwrite_policy(cli, fs, ndsfile, dest) {
+            Ok(true) => {}
+            Ok(false) => {
+                return FileOutcome::Skipped(Skip::Exists {
+                    dest: dest.to_path_buf(),
+                })
+            }
+            Err(e) => {
+                let e = io::Error::new(e.kind(), format!("'{}': {e}", dest.display()));
+                return FileOutcome::Failed(e.into());
+            }
+        }
+    }
+
+    if !cli.simulate {
+        let result = if cli.inplace {
+            ndsfile.trim()
+        } else if cli.resume {
+            let resumed = if cli.checksum {
+                ndsfile.resume_trim_with_name_verified(dest)
+            } else {
+                ndsfile.resume_trim_with_name(dest)
+            };
+            resumed.map(|offset| {
+                if offset > 0 {
+                    report_warning(dest, format!("note: resumed from byte {offset}"));
+                }
+            })
+        } else if let Some(Dedupe::Hardlink) = cli.dedupe {
+            trim_or_link(cli, ndsfile, dest, copies)
+        } else if let Some(algorithm) = cli.emit_checksum {
+            trim_with_checksum(cli, ndsfile, dest, algorithm)
+        } else if cli.atomic {
+            ndsfile.trim_with_name_atomic(dest)
         } else {
-            src.with_extension(&cli.extension)
+            ndsfile.trim_with_name(dest)
         };
+        if let Err(e) = result {
+            return FileOutcome::Failed(e);
+        }
+    }
+
+    FileOutcome::Trimmed(TrimmedRom {
+        dest: dest.to_path_buf(),
+        region: ndsfile.region(),
+        file_size: ndsfile.file_size(),
+        trimmed_size: ndsfile.trimmed_size(),
+        simulated: cli.simulate,
+    })
+}
+
+/// The trimmed copies written so far, by size and SHA-1 of their contents.
+type Copies = HashMap<(u64, String), PathBuf>;
+
+/// Trims `ndsfile` into `dest`, unless an identical copy was already written, in which case
+/// `dest` becomes a hard link to it.
+///
+/// Copies are looked up in and added to `copies`. If linking fails, e.g. across filesystems, the
+/// data is copied after all.
+fn trim_or_link<H: Handle>(
+    cli: &Cli,
+    ndsfile: &mut NdsFile<H>,
+    dest: &Path,
+    copies: &mut Copies,
+) -> Result<(), Error> {
+    let mut hasher = checksum::Hasher::new(checksum::Algorithm::Sha1);
+    io::copy(&mut ndsfile.trimmed_reader()?, &mut hasher)?;
+    let key = (ndsfile.trimmed_size(), hasher.finalize());
+
+    if let Some(existing) = copies.get(&key).filter(|&e| e != dest) {
+        match hard_link(existing, dest) {
+            Ok(()) => {
+                report_warning(dest, format!("note: linked to '{}'", existing.display()));
+                return Ok(());
+            }
+            Err(e) => report_warning(
+                dest,
+                format!("note: couldn't link to '{}', copying: {e}", existing.display()),
+            ),
+        }
+    }
+
+    if cli.atomic {
+        ndsfile.trim_with_name_atomic(dest)?;
+    } else {
+        // Writing through an existing link would change every file linked to it.
+        match fs::remove_file(dest) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        ndsfile.trim_with_name(dest)?;
+    }
+    copies.insert(key, dest.to_path_buf());
+
+    Ok(())
+}
+
+/// Makes `dest` a hard link to `existing`, replacing `dest` if it exists.
+///
+/// The link is created under a temporary name first, so `dest` is left alone on failure.
+fn hard_link(existing: &Path, dest: &Path) -> io::Result<()> {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(dest.file_name().unwrap_or_default());
+    tmp_name.push(format!(".{}.lnk", process::id()));
+    let tmp = dest.with_file_name(tmp_name);
+
+    fs::hard_link(existing, &tmp)?;
+    fs::rename(&tmp, dest).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Trims `ndsfile` into `dest`, and writes the checksum of the trimmed data next to it.
+///
+/// The data is hashed as it's copied, unless the copy is atomic, which takes a second read.
+fn trim_with_checksum<H: Handle>(
+    cli: &Cli,
+    ndsfile: &mut NdsFile<H>,
+    dest: &Path,
+    algorithm: checksum::Algorithm,
+) -> Result<(), Error> {
+    let mut hasher = checksum::Hasher::new(algorithm);
+    if cli.atomic {
+        ndsfile.trim_with_name_atomic(dest)?;
+        io::copy(&mut ndsfile.trimmed_reader()?, &mut hasher)?;
+    } else {
+        ndsfile.trim_with_name_tee(dest, &mut hasher)?;
+    }
+    checksum::write(dest, algorithm, &hasher.finalize())?;
 
-        let mut ndsfile = match NdsFile::open(src) {
-            Ok(f) => f,
+    Ok(())
+}
+
+/// Applies the finishing touches requested by `cli` to `dest`, the trimmed copy of `ndsfile`.
+fn finish_trim<H: Handle>(cli: &Cli, ndsfile: &mut NdsFile<H>, src: &Path, dest: &Path) {
+    if !cli.simulate {
+        if cli.preserve_perms && !cli.inplace {
+            if let Err(e) = copy_permissions(src, dest) {
+                report_warning(dest, format!("warning: permissions not preserved: {e}"));
+            }
+        }
+        if let Some(mode) = cli.mode {
+            if let Err(e) = set_mode(dest, mode) {
+                report_warning(dest, format!("warning: mode not set: {e}"));
+            }
+        }
+        if cli.make_readonly {
+            if let Err(e) = make_readonly(dest) {
+                report_warning(dest, format!("warning: couldn't make read-only: {e}"));
+            }
+        }
+    }
+
+    if cli.print_crc32 {
+        match ndsfile.crc32() {
+            Ok(crc) => println!("'{}': CRC-32 {:08X}", dest.display(), crc),
+            Err(e) => report_error(src, &e),
+        }
+    }
+    if cli.fingerprint {
+        match ndsfile.content_fingerprint() {
+            Ok(fingerprint) => println!(
+                "'{}': fingerprint {fingerprint:08X} over {} bytes",
+                dest.display(),
+                ndsfile.trimmed_size()
+            ),
+            Err(e) => report_error(src, &e),
+        }
+    }
+
+    if let Some(format) = cli.sidecar {
+        if let Err(e) = Sidecar::new(ndsfile, cli.simulate).write(dest, format) {
+            report_error(dest, &e.into());
+        }
+    }
+}
+
+/// Applies the overwrite policy in `cli` to the file at `dest`, if any, backing it up if needed.
+///
+/// Returns whether the trimmed copy of `ndsfile` should be written to `dest`.
+fn apply_overwrite_policy<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    ndsfile: &NdsFile<H>,
+    dest: &Path,
+) -> io::Result<bool> {
+    if !fs.exists(dest) {
+        return Ok(true);
+    }
+
+    match cli.overwrite {
+        Overwrite::Always => Ok(true),
+        Overwrite::Never => Ok(false),
+        Overwrite::IfSmaller => Ok(ndsfile.trimmed_size() < fs.len(dest)?),
+        Overwrite::Backup => {
+            if !cli.simulate {
+                let mut backup = dest.as_os_str().to_owned();
+                backup.push(".bak");
+                let backup = Path::new(&backup);
+                fs.rename(dest, backup)?;
+                if let Some(mode) = cli.mode {
+                    if let Err(e) = set_mode(backup, mode) {
+                        report_warning(backup, format!("warning: mode not set: {e}"));
+                    }
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Copies the permissions of `src` onto `dest`, along with its extended attributes where
+/// supported.
+fn copy_permissions(src: &Path, dest: &Path) -> io::Result<()> {
+    let permissions = fs::metadata(src)?.permissions();
+    // Attributes go first, as `dest` might become read-only.
+    copy_xattrs(src, dest)?;
+    fs::set_permissions(dest, permissions)
+}
+
+/// Marks the file at `path` as read-only.
+fn make_readonly(path: &Path) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(path, permissions)
+}
+
+/// Sets the permission bits of the file at `path` to `mode`.
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Fails to set the permission bits of a file, since they only exist on Unix-like systems.
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "file modes aren't supported on this platform",
+    ))
+}
+
+/// Copies the extended attributes of `src` onto `dest`.
+#[cfg(unix)]
+fn copy_xattrs(src: &Path, dest: &Path) -> io::Result<()> {
+    if !xattr::SUPPORTED_PLATFORM {
+        return Ok(());
+    }
+    let names = match xattr::list(src) {
+        Ok(names) => names,
+        Err(e) if e.kind() == ErrorKind::Unsupported => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in names {
+        if let Some(value) = xattr::get(src, &name)? {
+            xattr::set(dest, &name, &value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Does nothing, since extended attributes are only supported on Unix-like systems.
+#[cfg(not(unix))]
+fn copy_xattrs(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Runs `hook` after `src` was trimmed into `dest`, saving `saved` bytes.
+///
+/// The `{src}`, `{dest}` and `{saved}` placeholders are filled in within each word, without
+/// going through a shell, so paths never need escaping.
+fn run_hook(hook: &Hook, src: &Path, dest: &Path, saved: u64) -> io::Result<()> {
+    let (src, dest, saved) = (src.to_string_lossy(), dest.to_string_lossy(), saved.to_string());
+    let mut words = hook.0.iter().map(|w| {
+        w.replace("{src}", &src)
+            .replace("{dest}", &dest)
+            .replace("{saved}", &saved)
+    });
+    let program = words.next().unwrap_or_default();
+
+    let status = process::Command::new(&program)
+        .args(words)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("can't run post-hook '{program}': {e}")))?;
+    if !status.success() {
+        return Err(io::Error::other(format!("post-hook failed with {status}")));
+    }
+
+    Ok(())
+}
+
+/// Renames `src`, just trimmed in-place, after `template` filled in with `ndsfile`'s details.
+///
+/// The file keeps its extension, and isn't renamed if the new name is already taken.
+fn rename<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    template: &str,
+    ndsfile: NdsFile<H>,
+    src: &Path,
+) {
+    let mut name = fill_template(template, &ndsfile);
+    if let Some(ext) = src.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy());
+    }
+    let target = src.with_file_name(name);
+    // The handle must be closed before renaming, which some platforms don't allow otherwise.
+    drop(ndsfile);
+
+    if target == src {
+        return;
+    }
+    if fs.exists(&target) {
+        let e = io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("can't rename, '{}' already exists", target.display()),
+        );
+        report_error(src, &e.into());
+        return;
+    }
+    if !cli.simulate {
+        if let Err(e) = fs.rename(src, &target) {
+            report_error(src, &e.into());
+            return;
+        }
+    }
+
+    println!("'{}': renamed to '{}'", src.display(), target.display());
+}
+
+/// Replaces the `{title}`, `{gamecode}`, `{serial}` and `{region}` placeholders in `template`.
+///
+/// Characters that aren't allowed in file names on common platforms are replaced by `_`.
+fn fill_template<H: Handle>(template: &str, ndsfile: &NdsFile<H>) -> String {
+    template
+        .replace("{title}", &ndsfile.title())
+        .replace("{gamecode}", &ndsfile.gamecode())
+        .replace("{serial}", &ndsfile.serial())
+        .replace("{region}", ndsfile.region())
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Processes every ROM following `first` in a multi-ROM image like the first one, trimming each
+/// into a numbered copy of `src` next to `dest`, the copy of `first`.
+fn trim_following<H: Handle>(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    filter: &GamecodeFilter,
+    first: &NdsFile<H>,
+    src: &Path,
+    dest: &Path,
+    summary: &mut Summary,
+) {
+    let mut current = first.next_rom();
+    let mut n = 2;
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        let mut ndsfile = match current {
+            Ok(Some(f)) => f,
+            Ok(None) => break,
+            // The last ROM may end the image without any padding.
+            Err(Error::AlreadyTrimmed) => {
+                report_warning(src, format!("note: ROM {n} is already trimmed"));
+                break;
+            }
             Err(e) => {
-                eprintln!("'{}': {}", src.display(), e);
-                continue;
+                eprintln!(
+                    "'{}': ROM {}: {}",
+                    src.display(),
+                    n,
+                    term::paint(Tone::Failure, &e)
+                );
+                summary.record(src, &e);
+                break;
             }
         };
 
-        if !cli.simulate {
-            if cli.inplace {
-                if let Err(e) = ndsfile.trim() {
-                    eprintln!("'{}': {}", src.display(), e);
-                    continue;
+        let name = src.with_extension(format!("{n}.nds")).with_extension(&cli.extension);
+        let dest = dest.with_file_name(name.file_name().unwrap_or_default());
+        let outcome = process_file(cli, fs, filter, &mut ndsfile, src, &dest, &mut summary.copies);
+        report_outcome(cli, src, &outcome, summary);
+        if let FileOutcome::Trimmed(_) = outcome {
+            finish_trim(cli, &mut ndsfile, src, &dest);
+        }
+
+        current = ndsfile.next_rom();
+        n += 1;
+    }
+}
+
+/// Compares the trimmed data of `ndsfile` against the file at `other`, and reports the outcome.
+fn compare<H: Handle>(ndsfile: &mut NdsFile<H>, src: &Path, other: &Path) {
+    let result = File::open(other)
+        .map_err(Error::from)
+        .and_then(|mut f| ndsfile.compare_with(&mut f));
+
+    match result {
+        Ok(None) => println!(
+            "'{}': {}",
+            src.display(),
+            term::paint(Tone::Success, format!("matches '{}'", other.display()))
+        ),
+        Ok(Some(offset)) => println!(
+            "'{}': {}",
+            src.display(),
+            term::paint(
+                Tone::Failure,
+                format!("differs from '{}' at offset {:#x}", other.display(), offset)
+            )
+        ),
+        Err(e) => report_error(other, &e),
+    }
+}
+
+/// Reports an error that occurred while processing `path`.
+///
+/// Files that are skipped because they're already trimmed are reported as warnings.
+fn report_error(path: &Path, e: &Error) {
+    let tone = match e {
+        Error::AlreadyTrimmed => Tone::Warning,
+        _ => Tone::Failure,
+    };
+    eprintln!("'{}': {}", path.display(), term::paint(tone, e));
+}
+
+/// Loads a list of game codes from `path`, one per line, exiting on failure.
+///
+/// Blank lines and lines starting with `#` are ignored.
+fn load_gamecodes(path: &Path) -> HashSet<String> {
+    match fs::read_to_string(path) {
+        Ok(s) => s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_uppercase)
+            .collect(),
+        Err(e) => {
+            eprintln!("'{}': {}", path.display(), term::paint(Tone::Failure, e));
+            process::exit(1);
+        }
+    }
+}
+
+/// Expands a leading `~`, or a leading component naming an environment variable such as `$HOME`
+/// or `${ROMS}`, in the path `s`.
+///
+/// `~` and `$` anywhere else, e.g. in `Game $1 Edition.nds`, and undefined variables are kept as
+/// they are, so that file names containing them aren't mangled.
+fn expand(s: &str) -> Cow<'_, str> {
+    let (first, rest) = s.split_at(s.find(std::path::is_separator).unwrap_or(s.len()));
+    let name = first
+        .strip_prefix("${")
+        .and_then(|n| n.strip_suffix('}'))
+        .or_else(|| first.strip_prefix('$'));
+    match name {
+        Some(name) => env::var(name).map_or(Cow::Borrowed(s), |value| Cow::Owned(value + rest)),
+        None => shellexpand::tilde(s),
+    }
+}
+
+/// Loads a list of paths from `path`, one per line, exiting on failure.
+///
+/// A leading `~` or environment variable is expanded first, see [`expand`]. Lines
+/// containing `*`, `?` or `[` are then glob patterns, expanded in alphabetical order. Relative
+/// paths and patterns are resolved against the directory containing `path`, not the current one.
+/// Blank lines and lines starting with `#` are ignored.
+fn load_manifest(path: &Path) -> Vec<PathBuf> {
+    let contents = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("'{}': {}", path.display(), term::paint(Tone::Failure, e));
+            process::exit(1);
+        }
+    };
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let mut files = Vec::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let expanded = expand(line);
+        let line = expanded.as_ref();
+        if !line.contains(['*', '?', '[']) {
+            files.push(base.join(line));
+            continue;
+        }
+
+        let pattern = base
+            .to_str()
+            .map(|b| Path::new(&glob::Pattern::escape(b)).join(line));
+        let Some(matches) = pattern
+            .as_deref()
+            .and_then(Path::to_str)
+            .and_then(|p| glob::glob(p).ok())
+        else {
+            report_warning(path, format!("line {}: invalid pattern", n + 1));
+            continue;
+        };
+
+        let before = files.len();
+        for entry in matches {
+            match entry {
+                Ok(file) => files.push(file),
+                Err(e) => {
+                    let file = e.path().to_path_buf();
+                    report_error(&file, &io::Error::from(e).into());
                 }
-            } else if let Err(e) = ndsfile.trim_with_name(&dest) {
-                eprintln!("'{}': {}", src.display(), e);
-                continue;
             }
         }
+        if files.len() == before {
+            report_warning(path, format!("line {}: '{line}' matches nothing", n + 1));
+        }
+    }
 
-        println!(
-            "'{}': size reduced from {} to {}",
-            dest.display(),
-            ndsfile.file_size(),
-            ndsfile.trimmed_size()
+    files
+}
+
+/// Loads a size map from `path`, one `crc=size` entry per line, exiting on failure.
+///
+/// CRCs are hexadecimal, while sizes are either decimal or hexadecimal with a `0x` prefix. Blank
+/// lines and lines starting with `#` are ignored.
+fn load_size_map(path: &Path) -> SizeMap {
+    load_sizes(path, |crc| {
+        u32::from_str_radix(crc.strip_prefix("0x").unwrap_or(crc), 16).ok()
+    })
+}
+
+/// Loads a ROM database from `path`, one `serial=size` entry per line, exiting on failure.
+///
+/// Serials are formatted like [`NdsFile::serial`], e.g. `AMCE-01-v0`, and sizes like in
+/// [`load_size_map`].
+fn load_db(path: &Path) -> SizeDb {
+    load_sizes(path, |serial| Some(serial.to_owned()))
+}
+
+/// Loads `key=size` entries from `path`, parsing keys with `parse_key`, exiting on failure.
+///
+/// Sizes are either decimal or hexadecimal with a `0x` prefix. Blank lines and lines starting
+/// with `#` are ignored.
+fn load_sizes<K: Eq + Hash>(
+    path: &Path,
+    parse_key: impl Fn(&str) -> Option<K>,
+) -> HashMap<K, u64> {
+    let contents = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("'{}': {}", path.display(), term::paint(Tone::Failure, e));
+            process::exit(1);
+        }
+    };
+
+    let mut map = HashMap::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let entry = line.split_once('=').and_then(|(key, size)| {
+            let key = parse_key(key.trim())?;
+            let size = cli::parse_bytes(size.trim()).ok()?;
+            Some((key, size))
+        });
+        let Some((key, size)) = entry else {
+            eprintln!(
+                "'{}': {}",
+                path.display(),
+                term::paint(Tone::Failure, format!("line {}: invalid entry", n + 1))
+            );
+            process::exit(1);
+        };
+        map.insert(key, size);
+    }
+
+    map
+}
+
+/// Reports a warning about `path`.
+fn report_warning(path: &Path, msg: impl Display) {
+    eprintln!("'{}': {}", path.display(), term::paint(Tone::Warning, msg));
+}
+
+/// Ensures that the directories trimmed copies of `files`, found from `roots`, are written to
+/// exist and are writable, exiting otherwise.
+fn prepare_destinations(cli: &Cli, fs: &impl FileSystem, roots: &[PathBuf], files: &[PathBuf]) {
+    for dir in destination_dirs(cli, fs, roots, files) {
+        let created = if cli.preserve_tree {
+            fs.create_dir_all(&dir)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = created.and_then(|()| check_writable(fs, &dir)) {
+            eprintln!(
+                "'{}': {}",
+                dir.display(),
+                term::paint(Tone::Failure, format!("not writable: {e}"))
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns the distinct directories that trimmed copies of `files`, found from `roots`, are
+/// written to.
+fn destination_dirs(
+    cli: &Cli,
+    fs: &impl FileSystem,
+    roots: &[PathBuf],
+    files: &[PathBuf],
+) -> Vec<PathBuf> {
+    if let Some(dir) = cli.output_dir.as_ref().filter(|_| !cli.preserve_tree) {
+        return vec![dir.clone()];
+    }
+
+    let mut dirs: Vec<PathBuf> = files
+        .iter()
+        .map(|f| match destination(cli, fs, roots, f).parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        })
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Checks whether files can be created in `dir`, by creating and removing a temporary file.
+fn check_writable(fs: &impl FileSystem, dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".ndstrim-{}.tmp", process::id()));
+    fs.create_new(&probe)?;
+    fs.remove_file(&probe)
+}
+
+/// Computes the path that the trimmed copy of `src`, found from one of `roots`, is written to.
+fn destination(cli: &Cli, fs: &impl FileSystem, roots: &[PathBuf], src: &Path) -> PathBuf {
+    if cli.inplace {
+        return src.to_path_buf();
+    }
+
+    let dest = src.with_extension(&cli.extension);
+    match (&cli.output_dir, dest.file_name()) {
+        (Some(dir), Some(_)) if cli.preserve_tree => dir.join(relative_to_root(fs, roots, &dest)),
+        (Some(dir), Some(name)) => dir.join(name),
+        // Copies of remote files and block devices are written to the current directory.
+        (None, Some(name)) if is_url(src) || fs.is_block_device(src) => PathBuf::from(name),
+        _ => dest,
+    }
+}
+
+/// Returns `path` relative to the directory among `roots` it was found in, or its file name if it
+/// was passed directly.
+fn relative_to_root<'a>(fs: &impl FileSystem, roots: &[PathBuf], path: &'a Path) -> &'a Path {
+    roots
+        .iter()
+        .filter(|root| fs.is_dir(root))
+        .find_map(|root| path.strip_prefix(root).ok())
+        .or_else(|| path.file_name().map(Path::new))
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::vfs::MemFs;
+
+    /// Parses `args` as if passed on the command line.
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(iter::once("ndstrim").chain(args.iter().copied())).unwrap()
+    }
+
+    /// Opens a ROM holding 32 KiB of data, padded to 128 KiB.
+    fn open_rom() -> NdsFile<io::Cursor<Vec<u8>>> {
+        NdsFile::open_from_bytes(fixture::build_rom(0x8000, 0x20000)).unwrap()
+    }
+
+    #[test]
+    fn prepare_destinations_probes_each_directory() {
+        let fs = MemFs::default();
+        fs.add_file("a/x.nds", 0x100);
+        fs.add_file("b/y.nds", 0x100);
+        let cli = parse(&["a/x.nds", "b/y.nds"]);
+
+        prepare_destinations(&cli, &fs, &cli.files, &cli.files);
+
+        assert_eq!(fs.paths(), ["a", "a/x.nds", "b", "b/y.nds"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn prepare_destinations_uses_output_dir() {
+        let fs = MemFs::default();
+        fs.add_file("a/x.nds", 0x100);
+        fs.add_dir("out");
+        let cli = parse(&["-o", "out", "a/x.nds"]);
+
+        assert_eq!(
+            destination_dirs(&cli, &fs, &cli.files, &cli.files),
+            [PathBuf::from("out")]
+        );
+        prepare_destinations(&cli, &fs, &cli.files, &cli.files);
+
+        assert_eq!(fs.paths(), ["a", "a/x.nds", "out"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn backup_renames_existing_copy() {
+        let fs = MemFs::default();
+        fs.add_file("x.trim.nds", 0x100);
+        let cli = parse(&["--overwrite", "backup", "x.nds"]);
+        let dest = Path::new("x.trim.nds");
+
+        assert!(apply_overwrite_policy(&cli, &fs, &open_rom(), dest).unwrap());
+
+        assert!(!fs.exists(dest));
+        assert!(fs.is_file("x.trim.nds.bak"));
+    }
+
+    #[test]
+    fn backup_is_skipped_when_simulating() {
+        let fs = MemFs::default();
+        fs.add_file("x.trim.nds", 0x100);
+        let cli = parse(&["--overwrite", "backup", "--simulate", "x.nds"]);
+        let dest = Path::new("x.trim.nds");
+
+        assert!(apply_overwrite_policy(&cli, &fs, &open_rom(), dest).unwrap());
+
+        assert_eq!(fs.paths(), [PathBuf::from("x.trim.nds")]);
+    }
+
+    #[test]
+    fn existing_copies_are_kept_unless_replaced() {
+        let fs = MemFs::default();
+        fs.add_file("small.trim.nds", 0x100);
+        fs.add_file("large.trim.nds", 0x10000);
+        let rom = open_rom();
+        let keeps = |policy, dest: &str| {
+            let cli = parse(&["--overwrite", policy, "x.nds"]);
+            !apply_overwrite_policy(&cli, &fs, &rom, Path::new(dest)).unwrap()
+        };
+
+        assert!(keeps("never", "small.trim.nds"));
+        assert!(keeps("if-smaller", "small.trim.nds"));
+        assert!(!keeps("if-smaller", "large.trim.nds"));
+        assert!(!keeps("always", "small.trim.nds"));
+        assert!(!keeps("never", "missing.trim.nds"));
+    }
+
+    #[test]
+    fn preserve_tree_is_relative_to_expanded_roots() {
+        // Cargo defines this for test runs, so nothing has to be set here.
+        let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let fs = MemFs::default();
+        fs.add_file(dir.join("roms/sub/a.nds"), 0x100);
+        let cli = parse(&["-r", "--preserve-tree", "-o", "out", "$CARGO_MANIFEST_DIR/roms"]);
+
+        let inputs = collect_files(&cli, &fs);
+
+        assert_eq!(inputs.files, [dir.join("roms/sub/a.nds")]);
+        assert_eq!(
+            destination(&cli, &fs, &inputs.roots, &inputs.files[0]),
+            Path::new("out/sub/a.trim.nds")
         );
     }
+
+    #[test]
+    fn only_leading_variables_are_expanded() {
+        let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+
+        assert_eq!(expand("$CARGO_MANIFEST_DIR/a.nds"), format!("{dir}/a.nds"));
+        assert_eq!(expand("${CARGO_MANIFEST_DIR}/a.nds"), format!("{dir}/a.nds"));
+        assert_eq!(expand("$CARGO_MANIFEST_DIR"), dir);
+        for literal in [
+            "Game $CARGO_MANIFEST_DIR Edition.nds",
+            "a$CARGO_MANIFEST_DIR.nds",
+            "roms/$CARGO_MANIFEST_DIR/a.nds",
+            "$CARGO_MANIFEST_DIRX/a.nds",
+            "$NDSTRIM_TEST_UNDEFINED/a.nds",
+            "roms/~/a.nds",
+        ] {
+            assert_eq!(expand(literal), literal);
+        }
+    }
+
+    #[test]
+    fn strip_over_padding_trims_others_as_usual() {
+        let cli = parse(&["--strip-over-padding", "x.nds"]);
+        let mut rom = fixture::build_rom(0x8000, 0x30000);
+        rom[0x20000..].fill(0);
+        let mut ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        check_over_padding(&cli, &mut ndsfile, Path::new("x.nds")).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x20000);
+
+        let mut ndsfile = open_rom();
+        check_over_padding(&cli, &mut ndsfile, Path::new("x.nds")).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x8000);
+    }
+
+    /// Processes the second ROM of an image whose second ROM is padded to `second_size` bytes,
+    /// returning the summary.
+    fn follow(filter: &GamecodeFilter, second_size: usize) -> Summary {
+        let mut image = fixture::build_rom(0x8000, 0x20000);
+        image.extend(fixture::build_rom(0x8000, second_size));
+        let first = NdsFile::open_from_bytes(image).unwrap();
+        let cli = parse(&["--multirom", "--simulate", "x.nds"]);
+        let (src, dest) = (Path::new("x.nds"), Path::new("x.trim.nds"));
+
+        let mut summary = Summary::default();
+        trim_following(&cli, &MemFs::default(), filter, &first, src, dest, &mut summary);
+        summary
+    }
+
+    #[test]
+    fn following_roms_go_through_the_filter() {
+        let filter = GamecodeFilter {
+            skip: Some(HashSet::from(["NTRE".to_owned()])),
+            only: None,
+        };
+        let summary = follow(&filter, 0x20000);
+        assert_eq!(summary.excluded, 1);
+        assert!(summary.trimmed_by_region.is_empty());
+
+        let filter = GamecodeFilter {
+            skip: None,
+            only: None,
+        };
+        let summary = follow(&filter, 0x20000);
+        assert_eq!(summary.trimmed_by_region["USA"], (1, 0x18000));
+    }
+
+    #[test]
+    fn trailing_rom_without_padding_is_no_failure() {
+        let filter = GamecodeFilter {
+            skip: None,
+            only: None,
+        };
+        let summary = follow(&filter, 0x8000);
+        assert_eq!(summary.failed, 0);
+        assert_eq!(summary.already_trimmed, 0);
+    }
+
+    #[test]
+    fn trimmed_copies_are_only_skipped_in_directories() {
+        let fs = MemFs::default();
+        fs.add_file("roms/a.nds", 0x100);
+        fs.add_file("roms/a.trim.nds", 0x100);
+        fs.add_file("b.trim.nds", 0x100);
+
+        let inputs = collect_files(&parse(&["-r", "roms", "b.trim.nds"]), &fs);
+        assert_eq!(inputs.files, ["roms/a.nds", "b.trim.nds"].map(PathBuf::from));
+
+        let inputs = collect_files(&parse(&["-r", "--include-trimmed", "roms"]), &fs);
+        assert_eq!(inputs.files, ["roms/a.nds", "roms/a.trim.nds"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn failed_hook_only_aborts_if_requested() {
+        let dir = env::temp_dir().join(format!("ndstrim-test-{}-hook", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("x.trim.nds");
+        let filter = GamecodeFilter {
+            skip: None,
+            only: None,
+        };
+
+        for (args, aborted) in [
+            (&["--post-hook", "false", "x.nds"][..], false),
+            (&["--post-hook", "false", "--hook-failure", "abort", "x.nds"], true),
+            (&["--post-hook", "false", "--strict", "x.nds"], false),
+        ] {
+            let mut summary = Summary::default();
+            let cli = parse(args);
+            process(&cli, &RealFs, &filter, open_rom(), Path::new("x.nds"), &dest, &mut summary);
+            assert_eq!(summary.aborted, aborted);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fat_end_is_noted_if_it_differs_from_header() {
+        // A FAT at 0x2000 listing one file, which ends at `end`.
+        let open = |end: u32| {
+            let mut rom = fixture::build_rom(0x8000, 0x20000);
+            rom[0x48..0x4c].copy_from_slice(&0x2000_u32.to_le_bytes());
+            rom[0x4c..0x50].copy_from_slice(&8_u32.to_le_bytes());
+            let header_crc = crc::checksum(&rom[..0x15e]);
+            rom[0x15e..0x160].copy_from_slice(&header_crc.to_le_bytes());
+            rom[0x2000..0x2004].copy_from_slice(&0x3000_u32.to_le_bytes());
+            rom[0x2004..0x2008].copy_from_slice(&end.to_le_bytes());
+            NdsFile::options().tight(true).open_handle(io::Cursor::new(rom)).unwrap()
+        };
+
+        let note = fat_end_note(&open(0x9000));
+        assert_eq!(note.as_deref(), Some("note: last file ends at 36864, header declares 32768"));
+        assert_eq!(fat_end_note(&open(0x8000)), None);
+        assert_eq!(fat_end_note(&open_rom()), None);
+    }
+
+    #[test]
+    fn only_region_mismatches_are_strict_errors() {
+        let cli = parse(&["--check-naming", "--strict", "x.nds"]);
+        let inspects = |name: &str| inspect(&cli, &mut open_rom(), Path::new(name)).is_ok();
+
+        assert!(inspects("Ndstrimbench (USA).nds"));
+        assert!(inspects("Something Else (USA).nds"));
+        assert!(!inspects("Ndstrimbench (Japan).nds"));
+    }
 }
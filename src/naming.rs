@@ -0,0 +1,127 @@
+//! Consistency checks between ROM headers and file names following the No-Intro conventions.
+
+#![warn(clippy::pedantic)]
+
+use std::fmt;
+use std::path::Path;
+
+/// The regions used in No-Intro file names, e.g. `Title (Europe) (En,Fr).nds`.
+const REGIONS: [&str; 19] = [
+    "World",
+    "USA",
+    "Europe",
+    "Japan",
+    "Asia",
+    "Korea",
+    "China",
+    "Australia",
+    "Canada",
+    "France",
+    "Germany",
+    "Italy",
+    "Spain",
+    "Netherlands",
+    "Sweden",
+    "Norway",
+    "Denmark",
+    "Russia",
+    "Scandinavia",
+];
+
+/// Header regions that No-Intro may also name after the wider area they belong to.
+const EUROPEAN: [&str; 9] = [
+    "France",
+    "Germany",
+    "Italy",
+    "Spain",
+    "Netherlands",
+    "Sweden",
+    "Norway",
+    "Denmark",
+    "Russia",
+];
+
+/// A disagreement between a file name and the header of the ROM it holds.
+pub enum Mismatch {
+    /// The regions in the file name don't include the one derived from the game code.
+    Region { named: String, header: &'static str },
+    /// The title in the file name doesn't resemble the internal title.
+    Title { named: String, header: String },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Region { named, header } => {
+                write!(f, "named for {named}, but the game code is for {header}")
+            }
+            Mismatch::Title { named, header } => {
+                write!(f, "named '{named}', but the internal title is '{header}'")
+            }
+        }
+    }
+}
+
+/// Compares the No-Intro style name of `path` against the `region` and internal `title` of the
+/// ROM it holds.
+///
+/// Names without a region are ignored, as are parts of the name that can't be parsed. Internal
+/// titles are abbreviated to 12 characters, so the title only mismatches if the first word of
+/// `title` is missing from the name.
+pub fn check(path: &Path, region: &'static str, title: &str) -> Vec<Mismatch> {
+    let Some(stem) = path.file_stem().map(|s| s.to_string_lossy()) else {
+        return Vec::new();
+    };
+    // Stems of trimmed copies keep part of the extension, e.g. `Title (USA).trim`.
+    let (named_title, tags) = stem.split_once(" (").unwrap_or((&stem, ""));
+
+    // Names without a region don't follow the conventions at all.
+    let Some(named) = find_regions(tags) else {
+        return Vec::new();
+    };
+
+    let mut mismatches = Vec::new();
+    if !named.iter().any(|r| region_matches(r, region)) {
+        mismatches.push(Mismatch::Region {
+            named: named.join(", "),
+            header: region,
+        });
+    }
+
+    let word = normalize(title.split_whitespace().next().unwrap_or_default());
+    if word.len() >= 3 && !normalize(named_title).contains(&word) {
+        mismatches.push(Mismatch::Title {
+            named: named_title.to_owned(),
+            header: title.to_owned(),
+        });
+    }
+
+    mismatches
+}
+
+/// Returns the regions in the first parenthesized group of `tags` that only lists regions.
+fn find_regions(tags: &str) -> Option<Vec<&str>> {
+    tags.split(['(', ')'])
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .map(|g| g.split(',').map(str::trim).collect::<Vec<_>>())
+        .find(|g| g.iter().all(|r| REGIONS.contains(r)))
+}
+
+/// Checks whether the region `named` in a file name agrees with the `header` region.
+fn region_matches(named: &str, header: &str) -> bool {
+    header.split('/').any(|h| {
+        named == "World"
+            || named == h
+            || (named == "Europe" && EUROPEAN.contains(&h))
+            || (named == "Scandinavia" && ["Sweden", "Norway", "Denmark"].contains(&h))
+    }) || matches!(header, "International" | "Unknown")
+}
+
+/// Keeps only the letters and digits in `s`, in uppercase.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
@@ -2,19 +2,42 @@
 
 #![warn(clippy::pedantic)]
 
+use std::ffi::OsString;
 use std::fmt;
-use std::fs::File;
-use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::mem;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::result;
 
 use serde::Deserialize;
 
 use crate::crc;
+use crate::platform;
 
 type Result<T> = result::Result<T, Error>;
 
+/// The default size of the buffers used to copy ROM data.
+pub const DEFAULT_BUFFER_SIZE: usize = 0x10000;
+/// The minimum size of the buffers used to copy ROM data.
+pub const MIN_BUFFER_SIZE: usize = 0x1000;
+
+/// The Nintendo logo found in the headers of licensed ROMs.
+pub const NINTENDO_LOGO: [u8; 156] = [
+    0x24, 0xff, 0xae, 0x51, 0x69, 0x9a, 0xa2, 0x21, 0x3d, 0x84, 0x82, 0x0a, 0x84, 0xe4, 0x09, 0xad,
+    0x11, 0x24, 0x8b, 0x98, 0xc0, 0x81, 0x7f, 0x21, 0xa3, 0x52, 0xbe, 0x19, 0x93, 0x09, 0xce, 0x20,
+    0x10, 0x46, 0x4a, 0x4a, 0xf8, 0x27, 0x31, 0xec, 0x58, 0xc7, 0xe8, 0x33, 0x82, 0xe3, 0xce, 0xbf,
+    0x85, 0xf4, 0xdf, 0x94, 0xce, 0x4b, 0x09, 0xc1, 0x94, 0x56, 0x8a, 0xc0, 0x13, 0x72, 0xa7, 0xfc,
+    0x9f, 0x84, 0x4d, 0x73, 0xa3, 0xca, 0x9a, 0x61, 0x58, 0x97, 0xa3, 0x27, 0xfc, 0x03, 0x98, 0x76,
+    0x23, 0x1d, 0xc7, 0x61, 0x03, 0x04, 0xae, 0x56, 0xbf, 0x38, 0x84, 0x00, 0x40, 0xa7, 0x0e, 0xfd,
+    0xff, 0x52, 0xfe, 0x03, 0x6f, 0x95, 0x30, 0xf1, 0x97, 0xfb, 0xc0, 0x85, 0x60, 0xd6, 0x80, 0x25,
+    0xa9, 0x63, 0xbe, 0x03, 0x01, 0x4e, 0x38, 0xe2, 0xf9, 0xa2, 0x34, 0xff, 0xbb, 0x3e, 0x03, 0x44,
+    0x78, 0x00, 0x90, 0xcb, 0x88, 0x11, 0x3a, 0x94, 0x65, 0xc0, 0x7c, 0x63, 0x87, 0xf0, 0x3c, 0xaf,
+    0xd6, 0x25, 0xe4, 0x8b, 0x38, 0x0a, 0xac, 0x72, 0x21, 0xd4, 0xf8, 0x07,
+];
+
 /// A list of errors that may originate in this module.
 #[derive(Debug)]
 pub enum Error {
@@ -24,8 +47,37 @@ pub enum Error {
     Deserialization(bincode::Error),
     /// The header in the NDS file is malformed.
     BadHeader,
+    /// The header CRC doesn't match the header, although the Nintendo logo is valid, e.g. after
+    /// the title was patched.
+    StaleHeaderCrc { stored: u16, computed: u16 },
+    /// The NDS file is byte-swapped.
+    ByteSwapped,
+    /// The NDS file is in a format that can't be trimmed.
+    UnsupportedFormat(&'static str),
+    /// The NDS file is too small to contain a header.
+    TooSmall { actual: u64, needed: u64 },
     /// The NDS file is already trimmed.
     AlreadyTrimmed,
+    /// The sizes in the header are inconsistent with the NDS file.
+    InconsistentSize(SizeWarning),
+    /// The NDS file is smaller than its header declares, e.g. due to an interrupted download.
+    Truncated { actual: u64, expected: u64 },
+    /// The Nintendo logo in the header differs from the canonical one.
+    LogoMismatch,
+    /// The name of the NDS file disagrees with its header, e.g. about the region.
+    Misnamed(String),
+    /// The size declared in the header isn't a multiple of the expected alignment.
+    Unaligned { size: u64, alignment: u64 },
+    /// The data removed by trimming isn't all padding.
+    Irreversible { offset: u64 },
+    /// The requested trimmed size would cut into the ROM data or leave nothing to trim.
+    InvalidTrimSize {
+        size: u64,
+        minimum: u64,
+        maximum: u64,
+    },
+    /// The trailing bytes to reserve exceed the data trimming would remove.
+    ReservationTooLarge { reserved: u64, available: u64 },
 }
 
 impl fmt::Display for Error {
@@ -34,7 +86,57 @@ impl fmt::Display for Error {
             Error::Io(e) => write!(f, "{e}"),
             Error::Deserialization(e) => write!(f, "{e}"),
             Error::BadHeader => write!(f, "invalid header"),
+            Error::StaleHeaderCrc { stored, computed } => write!(
+                f,
+                "stale header CRC, {stored:#06x} is stored but the header has {computed:#06x}"
+            ),
+            Error::ByteSwapped => write!(f, "byte-swapped dump, swap every byte pair first"),
+            Error::UnsupportedFormat(format) => write!(f, "unsupported format: {format}"),
+            Error::TooSmall { actual, needed } => write!(
+                f,
+                "file too small, {actual} bytes found but a header needs {needed}"
+            ),
             Error::AlreadyTrimmed => write!(f, "already trimmed"),
+            Error::InconsistentSize(w) => write!(f, "{w}"),
+            Error::Truncated { actual, expected } => write!(
+                f,
+                "truncated, header declares {expected} bytes but file only contains {actual}"
+            ),
+            Error::LogoMismatch => write!(f, "Nintendo logo differs from the canonical one"),
+            Error::Misnamed(reason) => write!(f, "misnamed, {reason}"),
+            Error::Unaligned { size, alignment } => write!(
+                f,
+                "header declares {size} bytes, which isn't a multiple of {alignment}"
+            ),
+            Error::Irreversible { offset } => write!(
+                f,
+                "removed data isn't all padding, first other byte at {offset:#x}"
+            ),
+            Error::InvalidTrimSize {
+                size,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "can't trim to {size} bytes, must be at least {minimum} and below {maximum}"
+            ),
+            Error::ReservationTooLarge {
+                reserved,
+                available,
+            } => write!(
+                f,
+                "can't reserve {reserved} trailing bytes, trimming would only remove {available}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Deserialization(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -51,6 +153,124 @@ impl From<bincode::Error> for Error {
     }
 }
 
+/// Counts of the byte values in the data removed by trimming.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Padding {
+    /// The amount of `0xFF` bytes.
+    pub ff: u64,
+    /// The amount of `0x00` bytes.
+    pub zero: u64,
+    /// The amount of any other bytes, which are unlikely to be padding.
+    pub other: u64,
+    /// The offset of the first of the other bytes, relative to the start of the ROM.
+    pub first_other: Option<u64>,
+}
+
+/// A section of a ROM, as listed by [`NdsFile::layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// What the section holds, e.g. `ARM9 binary`.
+    pub name: &'static str,
+    /// Where the section lies, relative to the start of the ROM.
+    pub range: Range<u64>,
+}
+
+impl Section {
+    /// Creates a section named `name`, spanning `range`.
+    fn new(name: &'static str, range: Range<u64>) -> Self {
+        Self { name, range }
+    }
+}
+
+/// The sizes of a ROM before and after trimming, as found by [`estimate_savings`].
+#[derive(Debug, Clone)]
+pub struct Savings {
+    /// The path of the ROM.
+    pub path: PathBuf,
+    /// The current size of the ROM.
+    pub file_size: u64,
+    /// The size of the ROM once trimmed.
+    pub trimmed_size: u64,
+}
+
+impl Savings {
+    /// Returns the amount of bytes that trimming would save.
+    #[must_use]
+    pub fn saved(&self) -> u64 {
+        self.file_size - self.trimmed_size
+    }
+}
+
+/// The potential savings of trimming a set of ROMs, as returned by [`estimate_savings`].
+#[derive(Debug, Default)]
+pub struct SavingsReport {
+    /// The ROMs that could be opened, including already trimmed ones.
+    pub entries: Vec<Savings>,
+    /// The paths that couldn't be opened as ROMs, with the reason.
+    pub skipped: Vec<(PathBuf, Error)>,
+}
+
+impl SavingsReport {
+    /// Returns the combined size of the ROMs in `self`.
+    #[must_use]
+    pub fn file_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.file_size).sum()
+    }
+
+    /// Returns the combined size of the ROMs in `self` once trimmed.
+    #[must_use]
+    pub fn trimmed_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.trimmed_size).sum()
+    }
+
+    /// Returns the amount of bytes that trimming every ROM in `self` would save.
+    #[must_use]
+    pub fn saved(&self) -> u64 {
+        self.file_size() - self.trimmed_size()
+    }
+}
+
+/// What trimming a ROM would do, as returned by [`NdsFile::plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrimPlan {
+    /// The current size of the ROM.
+    pub file_size: u64,
+    /// The size of the ROM once trimmed.
+    pub trimmed_size: u64,
+    /// Whether the ROM has an RSA certificate that's kept.
+    pub cert_preserved: bool,
+    /// Whether the removed data is all `0xFF` or `0x00` padding, or `None` if it wasn't scanned.
+    pub pure_padding: Option<bool>,
+    /// What determined the trimmed size.
+    pub reason: TrimReason,
+}
+
+impl TrimPlan {
+    /// Returns the amount of bytes that trimming would remove.
+    #[must_use]
+    pub fn removed(&self) -> u64 {
+        self.file_size - self.trimmed_size
+    }
+}
+
+/// A list of inconsistencies between the sizes in a header and the NDS file.
+#[derive(Debug, Clone, Copy)]
+pub enum SizeWarning {
+    /// The header declares less data than the header itself occupies.
+    TooSmall { declared: u64, minimum: u64 },
+}
+
+impl fmt::Display for SizeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SizeWarning::TooSmall { declared, minimum } => write!(
+                f,
+                "header declares {declared} bytes, less than the minimum of {minimum}"
+            ),
+        }
+    }
+}
+
 /// The header of an NDS file.
 #[derive(Deserialize, PartialEq)]
 struct NtrTwlHeader {
@@ -58,8 +278,37 @@ struct NtrTwlHeader {
     gamecode: [u8; 4],
     makercode: [u8; 2],
     unitcode: u8,
-    #[serde(with = "serde_arrays")]
-    ignored0: [u8; 109],
+    encryption_seed: u8,
+    device_capacity: u8,
+    reserved0: [u8; 7],
+    twl_flags: u8,
+    region: u8,
+    rom_version: u8,
+    autostart: u8,
+    arm9_rom_offset: u32,
+    arm9_entry_address: u32,
+    arm9_ram_address: u32,
+    arm9_size: u32,
+    arm7_rom_offset: u32,
+    arm7_entry_address: u32,
+    arm7_ram_address: u32,
+    arm7_size: u32,
+    fnt_offset: u32,
+    fnt_size: u32,
+    fat_offset: u32,
+    fat_size: u32,
+    arm9_overlay_offset: u32,
+    arm9_overlay_size: u32,
+    arm7_overlay_offset: u32,
+    arm7_overlay_size: u32,
+    port_normal: u32,
+    port_key1: u32,
+    banner_offset: u32,
+    secure_area_crc: u16,
+    secure_area_delay: u16,
+    arm9_autoload: u32,
+    arm7_autoload: u32,
+    secure_area_disable: [u8; 8],
     ntr_rom_size: u32,
     header_size: u32,
     #[serde(with = "serde_arrays")]
@@ -74,24 +323,72 @@ struct NtrTwlHeader {
     #[serde(with = "serde_arrays")]
     ignored3: [u8; 144],
     ntr_twl_rom_size: u32,
+    ignored4: [u8; 28],
+    title_id: u64,
     #[serde(with = "serde_arrays")]
-    ignored4: [u8; 3564],
+    ignored5: [u8; 3528],
 }
 
 /// An NDS ROM header.
 impl NtrTwlHeader {
-    /// Loads a header from an open NDS ROM and verifies it.
-    fn from_file(f: &mut File) -> Result<Self> {
+    /// Loads a header from an open NDS ROM and verifies it according to `options`.
+    ///
+    /// Returns the header along with the CRC computed over it, which only differs from the stored
+    /// one if `options` accepts stale CRCs.
+    fn from_file<R: Read>(f: &mut R, options: &OpenOptions) -> Result<(Self, u16)> {
         let mut buf = vec![0; mem::size_of::<Self>()];
         f.read_exact(&mut buf)?;
 
-        let crc = crc::checksum(&buf[..0x15e]);
-        let header: Self = bincode::deserialize(&buf)?;
-        if header.header_crc != crc || !header.is_logo_valid() {
-            return Err(Error::BadHeader);
+        if let Some(header) = Self::verify(&buf, options.lenient)? {
+            return Ok((header, crc::checksum(&buf[..0x15e])));
+        }
+
+        // A valid logo means that the header was most likely edited without updating its CRC.
+        let (header, checks) = Self::check(&buf)?;
+        if checks.logo_crc_valid {
+            let computed = crc::checksum(&buf[..0x15e]);
+            if options.accept_stale_crc {
+                return Ok((header, computed));
+            }
+            return Err(Error::StaleHeaderCrc {
+                stored: header.header_crc,
+                computed,
+            });
+        }
+
+        // Some dumpers swap every pair of bytes, which is easily recognized.
+        for pair in buf.chunks_exact_mut(2) {
+            pair.swap(0, 1);
+        }
+        if Self::verify(&buf, options.lenient)?.is_some() {
+            return Err(Error::ByteSwapped);
+        }
+
+        Err(Error::BadHeader)
+    }
+
+    /// Deserializes a header from `buf`, returning `None` if it fails verification.
+    fn verify(buf: &[u8], lenient: bool) -> Result<Option<Self>> {
+        let (header, checks) = Self::check(buf)?;
+        if !checks.header_crc_valid || !(lenient || checks.logo_crc_valid) {
+            return Ok(None);
         }
 
-        Ok(header)
+        Ok(Some(header))
+    }
+
+    /// Deserializes a header from `buf`, running each of the checks done on open separately.
+    fn check(buf: &[u8]) -> Result<(Self, HeaderChecks)> {
+        let header: Self = bincode::deserialize(buf)?;
+        let checks = HeaderChecks {
+            logo_crc_valid: header.is_logo_valid(),
+            logo_canonical: header.logo_matches_canonical(),
+            header_crc_valid: header.header_crc == crc::checksum(&buf[..0x15e]),
+            sizes_plausible: header.check_sizes().is_none()
+                && header.used_size() <= header.device_capacity(),
+        };
+
+        Ok((header, checks))
     }
 
     /// Verifies `self`.
@@ -99,131 +396,1676 @@ impl NtrTwlHeader {
         crc::checksum(&self.nintendo_logo) == 0xcf56
     }
 
+    /// Checks whether `self`'s Nintendo logo is byte-for-byte identical to the canonical one.
+    fn logo_matches_canonical(&self) -> bool {
+        self.nintendo_logo == NINTENDO_LOGO
+    }
+
     /// Checks whether `self` belongs to an NTR-only ROM.
     fn is_ntr_only(&self) -> bool {
         self.unitcode == 0x00
     }
+
+    /// Checks whether `self` belongs to a title installed to the DSi's system memory, e.g.
+    /// DSiWare, rather than to a cartridge.
+    ///
+    /// Such titles have a TWL title ID whose high half is `0x0003xxxx`, with a non-zero category
+    /// in the lower bits, whereas cartridges always use `0x00030000`.
+    fn is_dsiware(&self) -> bool {
+        const CARTRIDGE: u32 = 0x0003_0000;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let high = (self.title_id >> 32) as u32;
+        !self.is_ntr_only() && high >> 16 == CARTRIDGE >> 16 && high != CARTRIDGE
+    }
+
+    /// Checks whether `self` belongs to homebrew, e.g. built with devkitARM's ndstool.
+    ///
+    /// Homebrew is recognized by an invalid Nintendo logo, or by the placeholder game code `####`
+    /// or maker code `00` that ndstool fills in by default.
+    fn is_homebrew(&self) -> bool {
+        !self.is_logo_valid()
+            || self.gamecode == *b"####"
+            || matches!(&self.makercode, b"00" | b"\0\0")
+    }
+
+    /// Returns the size of the ROM data declared by `self`, excluding the RSA certificate.
+    fn used_size(&self) -> u64 {
+        if self.is_ntr_only() {
+            self.ntr_rom_size.into()
+        } else {
+            self.ntr_twl_rom_size.into()
+        }
+    }
+
+    /// Checks whether the sizes declared by `self` are plausible.
+    ///
+    /// The header CRC doesn't guarantee this, so corrupt dumps may still pass verification.
+    fn check_sizes(&self) -> Option<SizeWarning> {
+        let declared = self.used_size();
+        let minimum = u64::from(self.header_size).max(mem::size_of::<Self>() as u64);
+
+        (declared < minimum).then_some(SizeWarning::TooSmall { declared, minimum })
+    }
+
+    /// Returns the capacity of the cartridge declared by `self`.
+    fn device_capacity(&self) -> u64 {
+        const MIN_CAPACITY: u64 = 0x20000;
+
+        MIN_CAPACITY
+            .checked_shl(self.device_capacity.into())
+            .unwrap_or(u64::MAX)
+    }
+}
+
+/// The outcome of each check done on a header, as returned by [`check_header`].
+///
+/// Headers that fail to open with [`Error::BadHeader`] may have been deliberately patched, e.g.
+/// with an intro, rather than corrupted, which the individual checks help tell apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct HeaderChecks {
+    /// Whether the Nintendo logo matches its expected CRC.
+    pub logo_crc_valid: bool,
+    /// Whether the Nintendo logo is byte-for-byte identical to the canonical one.
+    pub logo_canonical: bool,
+    /// Whether the header matches the CRC stored in it.
+    pub header_crc_valid: bool,
+    /// Whether the declared ROM size covers the header and fits the declared cartridge capacity.
+    pub sizes_plausible: bool,
+}
+
+impl HeaderChecks {
+    /// Checks whether the header looks deliberately patched rather than corrupt.
+    ///
+    /// Patching tools usually keep the sizes intact and either leave the logo alone or fix up the
+    /// header CRC afterwards, so exactly one of the CRCs fails.
+    #[must_use]
+    pub fn likely_patched(&self) -> bool {
+        self.sizes_plausible && self.logo_crc_valid != self.header_crc_valid
+    }
+}
+
+/// What determined the trimmed size of a ROM, as returned by [`NdsFile::trim_reason`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimReason {
+    /// The ROM size declared in the TWL part of the header, for DSi-enhanced and DSi-exclusive
+    /// ROMs.
+    TwlSize,
+    /// The ROM size declared in the NTR part of the header.
+    NtrSize,
+    /// The ROM size declared in the NTR part of the header, extended to keep the RSA certificate.
+    NtrSizeWithCert,
+    /// The end of the last overlay, which lies past the declared size.
+    Overlays,
+    /// The end of the banner, which lies past the declared size.
+    Banner,
+    /// The end of the last file in the file allocation table, see [`OpenOptions::tight`].
+    FatEnd,
+    /// The rounding applied to the size, see [`OpenOptions::rounding`].
+    Rounding,
+    /// The trailing bytes kept past the size, see [`OpenOptions::reserve_trailing`].
+    Reserved,
+    /// A size set with [`NdsFile::trim_to`].
+    Override,
+}
+
+impl fmt::Display for TrimReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrimReason::TwlSize => write!(f, "TWL ROM size in the header"),
+            TrimReason::NtrSize => write!(f, "NTR ROM size in the header"),
+            TrimReason::NtrSizeWithCert => {
+                write!(f, "NTR ROM size in the header, plus the RSA certificate")
+            }
+            TrimReason::Overlays => write!(f, "end of the last overlay"),
+            TrimReason::Banner => write!(f, "end of the banner"),
+            TrimReason::FatEnd => write!(f, "end of the last file in the file allocation table"),
+            TrimReason::Rounding => write!(f, "rounding mode"),
+            TrimReason::Reserved => write!(f, "reserved trailing bytes"),
+            TrimReason::Override => write!(f, "explicitly requested size"),
+        }
+    }
+}
+
+/// How the trimmed size of a ROM is finalized, as tools expect different conventions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// The size of the ROM data, never including the RSA certificate.
+    Exact,
+    /// The size of the ROM data, followed by the RSA certificate if there's one.
+    #[default]
+    Cert,
+    /// The size of [`Rounding::Cert`], rounded up to a multiple of this many bytes. Zero leaves
+    /// the size unchanged.
+    Sector(u64),
+    /// The capacity of the cartridge, so that only padding past it is removed.
+    Pow2,
+}
+
+impl Rounding {
+    /// Finalizes `size`, the size of the ROM data including any RSA certificate, for a ROM dumped
+    /// from a cartridge with `capacity` bytes.
+    fn apply(self, size: u64, capacity: u64) -> u64 {
+        match self {
+            Rounding::Exact | Rounding::Cert => size,
+            Rounding::Sector(sector) => size.checked_next_multiple_of(sector).unwrap_or(size),
+            Rounding::Pow2 if capacity >= size => capacity,
+            Rounding::Pow2 => size.next_power_of_two(),
+        }
+    }
+}
+
+/// Options to configure how an NDS file is opened.
+///
+/// This follows the same pattern as [`std::fs::OpenOptions`].
+#[derive(Clone, Debug)]
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
+pub struct OpenOptions {
+    /// Whether the file is opened without write access.
+    read_only: bool,
+    /// Whether headers with an invalid Nintendo logo are accepted.
+    lenient: bool,
+    /// Whether the RSA certificate is preserved.
+    preserve_cert: bool,
+    /// How the trimmed size is finalized.
+    rounding: Rounding,
+    /// Whether the trimmed size accounts for the file allocation table.
+    tight: bool,
+    /// Whether the trimmed size accounts for the banner.
+    keep_banner: bool,
+    /// The amount of bytes kept past the trimmed size.
+    reserve_trailing: u64,
+    /// The offset of the ROM within the file.
+    offset: u64,
+    /// Whether headers with a stale CRC but a valid Nintendo logo are accepted.
+    accept_stale_crc: bool,
+    /// Whether the file may hold further ROMs past this one.
+    multi_rom: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    /// Creates a blank set of options.
+    ///
+    /// By default, files are opened for reading and writing, headers are fully verified and RSA
+    /// certificates and banners are preserved.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            read_only: false,
+            lenient: false,
+            preserve_cert: true,
+            rounding: Rounding::Cert,
+            tight: false,
+            keep_banner: true,
+            reserve_trailing: 0,
+            offset: 0,
+            accept_stale_crc: false,
+            multi_rom: false,
+        }
+    }
+
+    /// Sets whether the file is opened without write access.
+    ///
+    /// Files opened this way can still be copied, but not trimmed in-place.
+    pub fn read_only(&mut self, read_only: bool) -> &mut Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets whether headers with an invalid Nintendo logo are accepted, e.g. in homebrew.
+    ///
+    /// The header CRC is still verified.
+    pub fn lenient(&mut self, lenient: bool) -> &mut Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets whether the RSA certificate is preserved.
+    ///
+    /// If `false`, the trimmed size never includes the RSA certificate, which may break
+    /// Download Play.
+    pub fn preserve_cert(&mut self, preserve_cert: bool) -> &mut Self {
+        self.preserve_cert = preserve_cert;
+        self
+    }
+
+    /// Sets how the trimmed size is finalized, see [`Rounding`].
+    ///
+    /// [`Rounding::Exact`] discards the RSA certificate like `preserve_cert(false)`, which may
+    /// break Download Play. Sizes are rounded after accounting for overlays, the banner and, with
+    /// [`OpenOptions::tight`], the file allocation table. Opening fails with
+    /// [`Error::AlreadyTrimmed`] if the rounded size isn't smaller than the file.
+    pub fn rounding(&mut self, rounding: Rounding) -> &mut Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Sets whether the trimmed size accounts for the end of the last file in the file allocation
+    /// table.
+    ///
+    /// If `true`, the FAT is parsed and the trimmed size is extended to cover every file listed in
+    /// it, should the size in the header fall short.
+    pub fn tight(&mut self, tight: bool) -> &mut Self {
+        self.tight = tight;
+        self
+    }
+
+    /// Sets whether the trimmed size accounts for the end of the banner.
+    ///
+    /// The banner normally lies within the ROM data declared in the header. If `true`, the
+    /// trimmed size is extended to cover it should it lie past that data, so that trimming never
+    /// cuts it. Banners said to extend past the end of the file are ignored.
+    pub fn keep_banner(&mut self, keep_banner: bool) -> &mut Self {
+        self.keep_banner = keep_banner;
+        self
+    }
+
+    /// Sets the amount of bytes kept past the trimmed size, whatever they contain, e.g. for a
+    /// footer appended to a trimmed ROM.
+    ///
+    /// The reservation is added once the size is finalized, see [`OpenOptions::rounding`].
+    /// Opening fails with [`Error::ReservationTooLarge`] if it exceeds the data trimming would
+    /// remove, and with [`Error::AlreadyTrimmed`] if it equals it.
+    pub fn reserve_trailing(&mut self, bytes: u64) -> &mut Self {
+        self.reserve_trailing = bytes;
+        self
+    }
+
+    /// Sets the offset of the ROM within the file, for ROMs embedded in larger images.
+    ///
+    /// All sizes are then relative to this offset, and data preceding it is never modified.
+    pub fn offset(&mut self, offset: u64) -> &mut Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets whether headers whose CRC doesn't match are accepted, as long as their Nintendo logo
+    /// is valid.
+    ///
+    /// Such headers were most likely edited without updating the CRC, e.g. by a title patch.
+    /// Otherwise, opening them fails with [`Error::StaleHeaderCrc`], whereas corrupt headers
+    /// always fail with [`Error::BadHeader`]. See [`NdsFile::fix_header_crc`].
+    pub fn accept_stale_crc(&mut self, accept_stale_crc: bool) -> &mut Self {
+        self.accept_stale_crc = accept_stale_crc;
+        self
+    }
+
+    /// Sets whether the file may hold further ROMs past this one, see [`NdsFile::next_rom`].
+    ///
+    /// If `true`, data right after a ROM that ends on the spacing of ROMs in multi-ROM files is
+    /// taken for the next ROM rather than a damaged RSA certificate. ROMs found with
+    /// [`NdsFile::next_rom`] are always opened this way.
+    pub fn multi_rom(&mut self, multi_rom: bool) -> &mut Self {
+        self.multi_rom = multi_rom;
+        self
+    }
+
+    /// Opens the NDS file at `path` with the options in `self`.
+    ///
+    /// On Unix-like systems, `path` may also be a block device, e.g. a cartridge exposed by a
+    /// reader. Since devices can't be truncated, they're always opened read-only, and their size
+    /// is that of the whole device, as reported by seeking to its end.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be opened, or if it isn't an untrimmed ROM. See
+    /// [`OpenOptions::open_handle`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::OpenOptions;
+    ///
+    /// let path = PathBuf::from("foo.nds");
+    /// let mut ndsfile = OpenOptions::new().lenient(true).open(&path)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn open(&self, path: &Path) -> Result<NdsFile> {
+        let handle = File::options()
+            .read(true)
+            .write(!self.read_only && !platform::is_block_device(path))
+            .open(path)?;
+        self.open_handle(handle)
+    }
+
+    /// Opens an NDS ROM from any [`Handle`] with the options in `self`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::BadHeader`] or [`Error::ByteSwapped`] if `handle` doesn't start with a
+    /// valid header at the configured offset, with [`Error::StaleHeaderCrc`] if only its CRC is
+    /// wrong, with [`Error::AlreadyTrimmed`] if there's nothing to
+    /// trim, and with [`Error::Truncated`] if the header declares more data than `handle`
+    /// contains.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs;
+    /// use std::io::Cursor;
+    /// use ndstrim::nds::OpenOptions;
+    ///
+    /// let data = Cursor::new(fs::read("foo.nds")?);
+    /// let mut ndsfile = OpenOptions::new().open_handle(data)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn open_handle<H: Handle>(&self, handle: H) -> Result<NdsFile<H>> {
+        NdsFile::from_handle(handle, self.offset, self)
+    }
+}
+
+/// A source of ROM data, such as a file.
+pub trait Handle: Read + Seek + Sized {
+    /// Truncates the data to `len` bytes.
+    ///
+    /// Sources that can't be modified return an error of kind [`ErrorKind::Unsupported`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the data can't be truncated.
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    /// Creates an independent handle to the same data.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the underlying resource can't be duplicated.
+    fn try_clone(&self) -> io::Result<Self>;
+
+    /// Overwrites the data at `offset` with `buf`.
+    ///
+    /// By default, this returns an error of kind [`ErrorKind::Unsupported`], for sources that
+    /// can't be modified.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the data can't be written.
+    fn write_all_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        let _ = (offset, buf);
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "this source can't be modified",
+        ))
+    }
+}
+
+impl Handle for File {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn write_all_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(buf)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        File::try_clone(self)
+    }
+}
+
+impl Handle for Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let len = usize::try_from(len).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+        self.get_mut().truncate(len);
+        Ok(())
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(self.clone())
+    }
+
+    fn write_all_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(buf)
+    }
 }
 
 /// An NDS file.
+///
+/// By default, the ROM data is read from a [`File`], but any [`Handle`] can be used.
 #[allow(clippy::module_name_repetitions)]
-pub struct NdsFile {
+pub struct NdsFile<H = File> {
     /// A handle to the open file.
-    handle: File,
+    handle: H,
+    /// The ROM's header.
+    header: NtrTwlHeader,
+    /// The offset of the ROM within the file.
+    base: u64,
+    /// The options `self` was opened with.
+    options: OpenOptions,
     /// The file's on-disk size.
     file_size: u64,
     /// The size of the ROM data.
     trimmed_size: u64,
+    /// What determined `trimmed_size`.
+    reason: TrimReason,
+    /// The size of the buffers used to copy data.
+    buffer_size: usize,
+    /// Whether copies skip over zero-filled chunks instead of writing them.
+    sparse: bool,
+    /// An inconsistency between the header and the file, if any.
+    size_warning: Option<SizeWarning>,
+    /// The end of the last file in the file allocation table, if it was parsed.
+    fat_end: Option<u64>,
+    /// The end of the last overlay listed in the overlay tables.
+    overlays_end: u64,
+    /// The range of the RSA certificate needed by Download Play, if any.
+    cert: Option<Range<u64>>,
+    /// The CRC computed over the header, which differs from the stored one if it's stale.
+    computed_header_crc: u16,
 }
 
 impl NdsFile {
-    /// Opens an NDS file for reading and writing.
+    /// Opens an NDS file for reading and writing, with the default options.
+    ///
+    /// # Errors
+    ///
+    /// Fails under the same conditions as [`OpenOptions::open`].
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// use std::path::PathBuf;
-    /// use nds::NdsFile;
+    /// use ndstrim::nds::NdsFile;
     ///
     /// let path = PathBuf::from("foo.nds");
-    /// let ndsfile = NdsFile::open(&path)?;
+    /// let mut ndsfile = NdsFile::open(&path)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
     /// ```
     pub fn open(path: &Path) -> Result<Self> {
-        let mut handle = File::options().read(true).write(true).open(path)?;
-        let header = NtrTwlHeader::from_file(&mut handle)?;
+        OpenOptions::new().open(path)
+    }
+
+    /// Returns a blank set of options to configure how an NDS file is opened.
+    ///
+    /// This is equivalent to [`OpenOptions::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let path = PathBuf::from("foo.nds");
+    /// let mut ndsfile = NdsFile::options().read_only(true).preserve_cert(false).open(&path)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    #[must_use]
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+}
+
+impl NdsFile<Cursor<Vec<u8>>> {
+    /// Opens an NDS ROM already held in memory, e.g. extracted from an archive, with the default
+    /// options.
+    ///
+    /// Trimming in-place truncates the data held by the returned [`NdsFile`] instead of a file.
+    ///
+    /// # Errors
+    ///
+    /// Fails under the same conditions as [`OpenOptions::open_handle`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let data = fs::read("foo.nds")?;
+    /// let mut ndsfile = NdsFile::open_from_bytes(data)?;
+    ///
+    /// ndsfile.trim()?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn open_from_bytes(data: Vec<u8>) -> Result<Self> {
+        OpenOptions::new().open_handle(Cursor::new(data))
+    }
+}
+
+impl<H: Handle> NdsFile<H> {
+    /// Loads a ROM whose header starts `base` bytes into `handle`.
+    fn from_handle(mut handle: H, base: u64, options: &OpenOptions) -> Result<Self> {
+        let file_size = handle.seek(SeekFrom::End(0))?.saturating_sub(base);
+        let needed = mem::size_of::<NtrTwlHeader>() as u64;
+        if file_size < needed {
+            return Err(Error::TooSmall {
+                actual: file_size,
+                needed,
+            });
+        }
 
-        let file_size = handle.metadata()?.len();
-        let trimmed_size = Self::compute_trimmed_size(&mut handle, &header)?;
+        handle.seek(SeekFrom::Start(base))?;
+        let (header, computed_header_crc) = NtrTwlHeader::from_file(&mut handle, options)?;
+
+        if header.used_size() > file_size {
+            return Err(Error::Truncated {
+                actual: file_size,
+                expected: header.used_size(),
+            });
+        }
+        let size_warning = header.check_sizes();
+
+        let preserve_cert = options.preserve_cert && options.rounding != Rounding::Exact;
+        let (mut trimmed_size, cert, mut reason) = Self::compute_trimmed_size(
+            &mut handle,
+            &header,
+            base,
+            preserve_cert,
+            options.multi_rom,
+        )?;
+        let mut extend = |end: u64, why: TrimReason| {
+            if end > trimmed_size {
+                trimmed_size = end;
+                reason = why;
+            }
+        };
+        let overlays_end = Self::find_overlays_end(&mut handle, &header, base)?;
+        extend(overlays_end, TrimReason::Overlays);
+        if options.keep_banner {
+            let banner_end = Self::find_banner_end(&mut handle, &header, base, file_size)?;
+            extend(banner_end, TrimReason::Banner);
+        }
+        let fat_end = if options.tight {
+            let fat_end = Self::find_fat_range(&mut handle, &header, base)?.end;
+            extend(fat_end, TrimReason::FatEnd);
+            Some(fat_end)
+        } else {
+            None
+        };
+        let rounded = options
+            .rounding
+            .apply(trimmed_size, header.device_capacity());
+        if rounded > trimmed_size {
+            trimmed_size = rounded;
+            reason = TrimReason::Rounding;
+        }
         if file_size <= trimmed_size {
             return Err(Error::AlreadyTrimmed);
         }
+        if options.reserve_trailing > 0 {
+            let available = file_size - trimmed_size;
+            if options.reserve_trailing > available {
+                return Err(Error::ReservationTooLarge {
+                    reserved: options.reserve_trailing,
+                    available,
+                });
+            }
+            trimmed_size += options.reserve_trailing;
+            reason = TrimReason::Reserved;
+            if file_size == trimmed_size {
+                return Err(Error::AlreadyTrimmed);
+            }
+        }
 
         Ok(Self {
             handle,
+            header,
+            base,
+            options: options.clone(),
             file_size,
             trimmed_size,
+            reason,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            sparse: false,
+            size_warning,
+            fat_end,
+            overlays_end,
+            cert,
+            computed_header_crc,
         })
     }
 
-    /// Checks whether the ROM contains RSA magic bytes.
+    /// Looks for another ROM following `self` in the same file, as found in multi-ROM images.
+    ///
+    /// Candidate headers are searched at every 128 KiB boundary after `self`'s trimmed data, up to
+    /// `self`'s device capacity. Returns `None` if no valid header is found.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from the handle fails, or if a header is found but the ROM it belongs to
+    /// can't be opened.
+    pub fn next_rom(&self) -> Result<Option<Self>> {
+        const ALIGNMENT: u64 = 0x20000;
+
+        let end = self.base + self.device_capacity().max(self.trimmed_size);
+        let mut offset = self.base + self.trimmed_size.next_multiple_of(ALIGNMENT);
+        let mut options = self.options.clone();
+        options.multi_rom(true);
+        while offset <= end {
+            match Self::from_handle(self.handle.try_clone()?, offset, &options) {
+                Ok(mut rom) => {
+                    rom.buffer_size = self.buffer_size;
+                    rom.sparse = self.sparse;
+                    return Ok(Some(rom));
+                }
+                Err(Error::BadHeader) => offset += ALIGNMENT,
+                Err(Error::TooSmall { .. }) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Looks for an RSA certificate at `offset`, relative to `base`, and returns where it starts.
     ///
     /// This is only relevant in certain ROMs, e.g. Mario Kart, for Download Play functionality.
-    fn has_cert(handle: &mut File, offset: u64) -> io::Result<bool> {
+    ///
+    /// The certificate normally starts with the `ac` magic bytes right at `offset`. Some dumps
+    /// align it further, so the magic is also looked for at the 4-byte aligned positions in the
+    /// following 0x200 bytes, as long as only uniform padding, i.e. all `0x00` or all `0xFF`,
+    /// precedes it.
+    ///
+    /// Since some dumps have the magic damaged or zeroed, a certificate is also assumed at
+    /// `offset` if the 0x88 bytes where it would lie aren't uniform padding, as keeping those
+    /// bytes is cheap while losing a certificate breaks Download Play. If `multi_rom` is set, this
+    /// doesn't apply at offsets aligned to the spacing of ROMs in multi-ROM files, where the data
+    /// may belong to the next ROM instead.
+    fn find_cert(
+        handle: &mut H,
+        base: u64,
+        offset: u64,
+        multi_rom: bool,
+    ) -> io::Result<Option<u64>> {
         const RSA_MAGIC: [u8; 2] = [0x61, 0x63]; // Equals "ac".
+        const RSA_SIZE: usize = 0x88;
+        const MAX_SHIFT: usize = 0x200;
+        const ROM_ALIGNMENT: u64 = 0x20000;
+
+        let mut buf = [0; MAX_SHIFT + RSA_MAGIC.len()];
+        handle.seek(SeekFrom::Start(base + offset))?;
+        handle.read_exact(&mut buf[..RSA_MAGIC.len()])?;
+        if buf[..RSA_MAGIC.len()] == RSA_MAGIC {
+            return Ok(Some(offset));
+        }
+        let len = RSA_MAGIC.len() + read_full(handle, &mut buf[RSA_MAGIC.len()..])?;
+        let buf = &buf[..len];
+
+        let is_padding =
+            |data: &[u8]| data.iter().all(|&b| b == 0x00) || data.iter().all(|&b| b == 0xff);
+        let shifted = (4..=MAX_SHIFT)
+            .step_by(4)
+            .take_while(|&shift| shift + RSA_MAGIC.len() <= len && is_padding(&buf[..shift]))
+            .find(|&shift| buf[shift..].starts_with(&RSA_MAGIC));
+        if let Some(shift) = shifted {
+            return Ok(Some(offset + shift as u64));
+        }
+
+        // A truncated certificate can't be told apart from leftover data.
+        if (multi_rom && offset.is_multiple_of(ROM_ALIGNMENT)) || len < RSA_SIZE {
+            return Ok(None);
+        }
+        Ok((!is_padding(&buf[..RSA_SIZE])).then_some(offset))
+    }
+
+    /// Computes the range spanned by the files listed in the file allocation table.
+    ///
+    /// Each FAT entry is a pair of little-endian start and end offsets, where the end is
+    /// exclusive. Unused entries, whose end doesn't follow their start, are skipped. The range is
+    /// empty if there are no files.
+    fn find_fat_range(handle: &mut H, header: &NtrTwlHeader, base: u64) -> Result<Range<u64>> {
+        handle.seek(SeekFrom::Start(base + u64::from(header.fat_offset)))?;
+        let mut fat = BufReader::new(Read::by_ref(handle).take(header.fat_size.into()));
+
+        let mut start = u32::MAX;
+        let mut end = 0;
+        let mut entry = [0; 8];
+        for _ in 0..header.fat_size / 8 {
+            fat.read_exact(&mut entry)?;
+            let (start_bytes, end_bytes) = entry.split_at(4);
+            let file_start = u32::from_le_bytes(start_bytes.try_into().unwrap_or_default());
+            let file_end = u32::from_le_bytes(end_bytes.try_into().unwrap_or_default());
+            if file_end > file_start {
+                start = start.min(file_start);
+                end = end.max(file_end);
+            }
+        }
+
+        Ok(u64::from(start.min(end))..end.into())
+    }
+
+    /// Computes the offset where the last overlay listed in the ARM9 and ARM7 overlay tables ends.
+    ///
+    /// Each overlay table entry is 32 bytes long, with the overlay's file ID at offset 0x18. The
+    /// file's extent is then looked up in the file allocation table. Entries cut off by the end
+    /// of the file and IDs outside the file allocation table are skipped, as they can't be
+    /// resolved.
+    fn find_overlays_end(handle: &mut H, header: &NtrTwlHeader, base: u64) -> Result<u64> {
+        const ENTRY_SIZE: usize = 32;
+        const FILE_ID_OFFSET: usize = 0x18;
+
+        let mut file_ids = Vec::new();
+        let tables = [
+            (header.arm9_overlay_offset, header.arm9_overlay_size),
+            (header.arm7_overlay_offset, header.arm7_overlay_size),
+        ];
+        for (offset, size) in tables {
+            if size == 0 {
+                continue;
+            }
+            handle.seek(SeekFrom::Start(base + u64::from(offset)))?;
+            let mut table = Vec::new();
+            Read::by_ref(handle)
+                .take(size.into())
+                .read_to_end(&mut table)?;
+            for entry in table.chunks_exact(ENTRY_SIZE) {
+                let id = &entry[FILE_ID_OFFSET..FILE_ID_OFFSET + 4];
+                file_ids.push(u32::from_le_bytes(id.try_into().unwrap_or_default()));
+            }
+        }
 
-        let mut buf = vec![0; 2];
-        handle.seek(SeekFrom::Start(offset))?;
-        handle.read_exact(&mut buf)?;
+        let mut end = 0;
+        let mut entry = [0; 8];
+        for id in file_ids {
+            if u64::from(id) >= u64::from(header.fat_size / 8) {
+                continue;
+            }
+            let offset = u64::from(header.fat_offset) + u64::from(id) * 8;
+            handle.seek(SeekFrom::Start(base + offset))?;
+            match handle.read_exact(&mut entry) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => continue,
+                Err(e) => return Err(e.into()),
+            }
+            let (start_bytes, end_bytes) = entry.split_at(4);
+            let file_start = u32::from_le_bytes(start_bytes.try_into().unwrap_or_default());
+            let file_end = u32::from_le_bytes(end_bytes.try_into().unwrap_or_default());
+            if file_end > file_start {
+                end = end.max(file_end);
+            }
+        }
 
-        Ok(buf == RSA_MAGIC)
+        Ok(end.into())
     }
 
-    /// Computes the size of the ROM contents.
+    /// Computes the size of the ROM contents, along with the range of its RSA certificate, if any,
+    /// and what determined the size.
     ///
     /// Generally, this matches the size reported in the header, unless the ROM contains a RSA
     /// certificate.
-    /// In such a case, the size should include 0x88 more bytes to preserve Download Play.
-    fn compute_trimmed_size(handle: &mut File, header: &NtrTwlHeader) -> Result<u64> {
+    /// In such a case, the size should extend to the end of the certificate to preserve Download
+    /// Play, unless `preserve_cert` is `false`. See [`OpenOptions::multi_rom`] for `multi_rom`.
+    fn compute_trimmed_size(
+        handle: &mut H,
+        header: &NtrTwlHeader,
+        base: u64,
+        preserve_cert: bool,
+        multi_rom: bool,
+    ) -> Result<(u64, Option<Range<u64>>, TrimReason)> {
         const RSA_SIZE: u64 = 0x88;
 
+        let mut trimsize = header.used_size();
         if !header.is_ntr_only() {
-            return Ok(header.ntr_twl_rom_size.into());
+            return Ok((trimsize, None, TrimReason::TwlSize));
         }
 
-        let mut trimsize = header.ntr_rom_size.into();
-
-        let has_cert = Self::has_cert(handle, trimsize).map_err(|e| match e.kind() {
+        let cert_offset = match Self::find_cert(handle, base, trimsize, multi_rom) {
+            Ok(cert_offset) => cert_offset,
             // Assume the file has already been trimmed if EOF is encountered.
-            ErrorKind::UnexpectedEof => Error::AlreadyTrimmed,
-            _ => e.into(),
-        })?;
-        if has_cert {
-            trimsize += RSA_SIZE;
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && preserve_cert => {
+                return Err(Error::AlreadyTrimmed)
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            Err(e) => return Err(e.into()),
+        };
+        let Some(cert_offset) = cert_offset else {
+            return Ok((trimsize, None, TrimReason::NtrSize));
+        };
+
+        let cert = cert_offset..cert_offset + RSA_SIZE;
+        let mut reason = TrimReason::NtrSize;
+        if preserve_cert {
+            trimsize = cert.end;
+            reason = TrimReason::NtrSizeWithCert;
         }
 
-        Ok(trimsize)
+        Ok((trimsize, Some(cert), reason))
     }
 
     /// Trims `self` in-place. This is irreversible.
     ///
+    /// If `self` doesn't start at the beginning of the file, any data following it is discarded.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the handle can't be truncated, e.g. if `self` was opened read-only.
+    ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// use std::path::PathBuf;
-    /// use nds::NdsFile;
+    /// use ndstrim::nds::NdsFile;
     ///
     /// let path = PathBuf::from("foo.nds");
-    /// let ndsfile = NdsFile::open(&path)?;
+    /// let mut ndsfile = NdsFile::open(&path)?;
     ///
     /// ndsfile.trim()?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
     /// ```
     pub fn trim(&mut self) -> Result<()> {
-        self.handle.set_len(self.trimmed_size)?;
+        self.handle.set_len(self.base + self.trimmed_size)?;
         Ok(())
     }
 
     /// Copies `self`'s data into `dest`.
     ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` or writing to `dest` fails. `dest` is removed on failure, so
+    /// that a partial copy isn't mistaken for a trimmed ROM.
+    ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// use std::path::PathBuf;
-    /// use nds::NdsFile;
+    /// use ndstrim::nds::NdsFile;
     ///
     /// let src = PathBuf::from("foo.nds");
     /// let dest = PathBuf::from("bar.nds");
-    /// let ndsfile = NdsFile::open(&src)?;
+    /// let mut ndsfile = NdsFile::open(&src)?;
     ///
     /// ndsfile.trim_with_name(&dest)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
     /// ```
     pub fn trim_with_name(&mut self, dest: &Path) -> Result<()> {
+        self.trim_with_name_progress(dest, |_, _| {})
+    }
+
+    /// Copies `self`'s data into `dest`, reporting progress through `cb`.
+    ///
+    /// After each chunk is written, `cb` is called with the amount of bytes copied so far and the
+    /// total amount of bytes to copy.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` or writing to `dest` fails. `dest` is removed on failure.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let src = PathBuf::from("foo.nds");
+    /// let dest = PathBuf::from("bar.nds");
+    /// let mut ndsfile = NdsFile::open(&src)?;
+    ///
+    /// ndsfile.trim_with_name_progress(&dest, |copied, total| {
+    ///     println!("{copied}/{total}");
+    /// })?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn trim_with_name_progress(&mut self, dest: &Path, cb: impl FnMut(u64, u64)) -> Result<()> {
+        self.copy_to(dest, &mut io::sink(), cb)
+    }
+
+    /// Copies `self`'s data into `dest`, also writing it to `tee` along the way.
+    ///
+    /// This allows processing the trimmed data, e.g. hashing it, without reading it twice.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self`, or writing to `dest` or `tee` fails. `dest` is removed on
+    /// failure.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let src = PathBuf::from("foo.nds");
+    /// let dest = PathBuf::from("bar.nds");
+    /// let mut ndsfile = NdsFile::open(&src)?;
+    ///
+    /// let mut copy = Vec::new();
+    /// ndsfile.trim_with_name_tee(&dest, &mut copy)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn trim_with_name_tee(&mut self, dest: &Path, tee: &mut impl Write) -> Result<()> {
+        self.copy_to(dest, tee, |_, _| {})
+    }
+
+    /// Copies `self`'s data into `dest` and `tee`, reporting progress through `cb`.
+    ///
+    /// `dest` is removed on failure, e.g. if reading from failing media stops partway.
+    fn copy_to(
+        &mut self,
+        dest: &Path,
+        tee: &mut impl Write,
+        cb: impl FnMut(u64, u64),
+    ) -> Result<()> {
         let mut out = File::create(dest)?;
-        self.handle.seek(SeekFrom::Start(0))?;
-        io::copy(&mut self.handle.by_ref().take(self.trimmed_size), &mut out)?;
+        let result = self.write_trimmed(&mut out, tee, cb);
+        if result.is_err() {
+            drop(out);
+            let _ = fs::remove_file(dest);
+        }
+
+        result
+    }
+
+    /// Writes `self`'s data into `out` and `tee`, reporting progress through `cb`.
+    fn write_trimmed(
+        &mut self,
+        out: &mut File,
+        tee: &mut impl Write,
+        mut cb: impl FnMut(u64, u64),
+    ) -> Result<()> {
+        let total = self.trimmed_size;
+        let mut buf = vec![0; self.buffer_size];
+
+        let sparse = self.sparse;
+        let mut src = self.trimmed_reader()?;
+        let mut copied = 0;
+        loop {
+            let n = read_full(&mut src, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            if sparse && buf[..n].iter().all(|&b| b == 0) {
+                out.seek(SeekFrom::Start(copied + n as u64))?;
+            } else {
+                out.write_all(&buf[..n])?;
+            }
+            tee.write_all(&buf[..n])?;
+            copied += n as u64;
+            cb(copied, total);
+        }
+        if sparse {
+            // A trailing hole isn't part of the file until its length is set.
+            out.set_len(copied)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies `self`'s data into `dest`, keeping what an interrupted copy already wrote there.
+    ///
+    /// The existing contents of `dest` are assumed to be a prefix of `self`'s data, so the copy
+    /// resumes right past them without reading them again. See
+    /// [`NdsFile::resume_trim_with_name_verified`] to check them first. If `dest` doesn't exist,
+    /// it's created. Returns the offset the copy resumed from.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self`, or writing to `dest` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let src = PathBuf::from("foo.nds");
+    /// let dest = PathBuf::from("bar.nds");
+    /// let mut ndsfile = NdsFile::open(&src)?;
+    ///
+    /// let offset = ndsfile.resume_trim_with_name(&dest)?;
+    /// println!("resumed from {offset}");
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn resume_trim_with_name(&mut self, dest: &Path) -> Result<u64> {
+        self.resume_copy(dest, false)
+    }
+
+    /// Copies `self`'s data into `dest` like [`NdsFile::resume_trim_with_name`], first comparing
+    /// the existing contents of `dest` against `self`'s data.
+    ///
+    /// The copy resumes from the first byte that differs, so a corrupt or unrelated file is simply
+    /// overwritten. This reads the existing contents in full, though.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self`, or reading from or writing to `dest` fails.
+    pub fn resume_trim_with_name_verified(&mut self, dest: &Path) -> Result<u64> {
+        self.resume_copy(dest, true)
+    }
+
+    /// Copies `self`'s data into `dest` past what's already there, comparing that against
+    /// `self`'s data first if `verify` is set. Returns the offset the copy resumed from.
+    fn resume_copy(&mut self, dest: &Path, verify: bool) -> Result<u64> {
+        let mut out = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dest)?;
+        let existing = out.metadata()?.len().min(self.trimmed_size);
+
+        let offset = if verify {
+            let mut prefix = Read::by_ref(&mut out).take(existing);
+            self.compare_with(&mut prefix)?.unwrap_or(existing).min(existing)
+        } else {
+            existing
+        };
+
+        let mut buf = vec![0; self.buffer_size];
+        out.seek(SeekFrom::Start(offset))?;
+        self.handle.seek(SeekFrom::Start(self.base + offset))?;
+        let mut rest = Read::by_ref(&mut self.handle).take(self.trimmed_size - offset);
+        loop {
+            let n = read_full(&mut rest, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            out.write_all(&buf[..n])?;
+        }
+        out.set_len(self.trimmed_size)?;
+        out.sync_all()?;
+
+        Ok(offset)
+    }
+
+    /// Atomically copies `self`'s data into `dest`.
+    ///
+    /// The data is first written to a temporary file in `dest`'s directory, which is then renamed
+    /// to `dest`. Since the temporary file always lives on the same filesystem as `dest`, this
+    /// works even when `self` is on a different device, where a rename would fail with `EXDEV`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self`, writing the temporary file or renaming it fails. The temporary
+    /// file is removed on failure.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let src = PathBuf::from("foo.nds");
+    /// let dest = PathBuf::from("/mnt/other/bar.nds");
+    /// let mut ndsfile = NdsFile::open(&src)?;
+    ///
+    /// ndsfile.trim_with_name_atomic(&dest)?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn trim_with_name_atomic(&mut self, dest: &Path) -> Result<()> {
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(dest.file_name().unwrap_or_default());
+        tmp_name.push(format!(".{}.tmp", process::id()));
+        let tmp = dest.with_file_name(tmp_name);
+
+        let result = File::create(&tmp).map_err(Error::from).and_then(|mut out| {
+            self.write_trimmed(&mut out, &mut io::sink(), |_, _| {})?;
+            // Sync through the handle that did the writing, as read-only handles can't be synced
+            // everywhere, e.g. on Windows.
+            out.sync_all()?;
+            drop(out);
+            fs::rename(&tmp, dest)?;
+            Ok(())
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+
+        result
+    }
+
+    /// Compares `self`'s trimmed data with the contents of `other`.
+    ///
+    /// Returns the offset of the first differing byte, or `None` if `other` matches exactly.
+    /// If one of the two is a prefix of the other, the offset is the length of the shorter one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` or `other` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let src = PathBuf::from("foo.nds");
+    /// let mut ndsfile = NdsFile::open(&src)?;
+    /// let mut trimmed = File::open("foo.trim.nds")?;
+    ///
+    /// if let Some(offset) = ndsfile.compare_with(&mut trimmed)? {
+    ///     println!("mismatch at {offset:#x}");
+    /// }
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn compare_with<R: Read>(&mut self, other: &mut R) -> Result<Option<u64>> {
+        let mut buf_ours = vec![0; self.buffer_size];
+        let mut buf_theirs = vec![0; self.buffer_size];
+        let mut ours = self.trimmed_reader()?;
+        let mut offset = 0;
+
+        loop {
+            let n = read_full(&mut ours, &mut buf_ours)?;
+            let m = read_full(other, &mut buf_theirs)?;
+            if let Some(i) = buf_ours[..n.min(m)]
+                .iter()
+                .zip(&buf_theirs[..n.min(m)])
+                .position(|(a, b)| a != b)
+            {
+                return Ok(Some(offset + i as u64));
+            }
+            if n != m {
+                return Ok(Some(offset + n.min(m) as u64));
+            }
+            if n == 0 {
+                return Ok(None);
+            }
+            offset += n as u64;
+        }
+    }
+
+    /// Computes the CRC-32 of `self`'s trimmed data.
+    ///
+    /// This matches the CRC-32 of the file produced by [`NdsFile::trim_with_name`], and can be
+    /// looked up in No-Intro DATs.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn crc32(&mut self) -> Result<u32> {
+        let mut buf = vec![0; self.buffer_size];
+        let mut data = self.trimmed_reader()?;
+        let mut crc = crc::Crc32::new();
+        loop {
+            let n = read_full(&mut data, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            crc.update(&buf[..n]);
+        }
+
+        Ok(crc.finalize())
+    }
+
+    /// Computes a fingerprint identifying `self`'s trimmed data.
+    ///
+    /// This is the standard CRC-32 of the bytes from the start of the header up to
+    /// [`NdsFile::trimmed_size`], the same as [`NdsFile::crc32`], so other tools can reproduce it
+    /// from the trimmed file alone.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn content_fingerprint(&mut self) -> Result<u32> {
+        self.crc32()
+    }
+
+    /// Describes what trimming `self` would do, without changing anything.
+    ///
+    /// The removed data isn't scanned, so [`TrimPlan::pure_padding`] is `None`. See
+    /// [`NdsFile::plan_verified`].
+    #[must_use]
+    pub fn plan(&self) -> TrimPlan {
+        TrimPlan {
+            file_size: self.file_size,
+            trimmed_size: self.trimmed_size,
+            cert_preserved: self
+                .cert
+                .as_ref()
+                .is_some_and(|c| c.end <= self.trimmed_size),
+            pure_padding: None,
+            reason: self.reason,
+        }
+    }
+
+    /// Describes what trimming `self` would do like [`NdsFile::plan`], also scanning the removed
+    /// data to tell whether it's all padding.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn plan_verified(&mut self) -> Result<TrimPlan> {
+        let padding = self.scan_padding()?;
+        Ok(TrimPlan {
+            pure_padding: Some(padding.other == 0),
+            ..self.plan()
+        })
+    }
+
+    /// Counts the byte values in the data that trimming `self` would remove.
+    ///
+    /// Well-formed dumps are padded with `0xFF` or `0x00`, so other bytes suggest that the
+    /// header declares a wrong size.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn scan_padding(&mut self) -> Result<Padding> {
+        self.copy_tail(&mut io::sink())
+    }
+
+    /// Copies the data that trimming `self` would remove into `dest`, counting its byte values
+    /// like [`NdsFile::scan_padding`].
+    ///
+    /// This keeps the removed data around, e.g. to inspect dumps that may hold more than padding
+    /// past the declared size.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` or writing to `dest` fails.
+    pub fn copy_tail(&mut self, dest: &mut impl Write) -> Result<Padding> {
+        let mut buf = vec![0; self.buffer_size];
+        self.handle
+            .seek(SeekFrom::Start(self.base + self.trimmed_size))?;
+        let mut tail = Read::by_ref(&mut self.handle).take(self.file_size - self.trimmed_size);
+        let mut padding = Padding::default();
+        let mut offset = self.trimmed_size;
+        loop {
+            let n = read_full(&mut tail, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            dest.write_all(&buf[..n])?;
+            for &b in &buf[..n] {
+                match b {
+                    0xff => padding.ff += 1,
+                    0x00 => padding.zero += 1,
+                    _ => {
+                        padding.first_other.get_or_insert(offset);
+                        padding.other += 1;
+                    }
+                }
+                offset += 1;
+            }
+        }
+
+        Ok(padding)
+    }
+
+    /// Lists the sections of `self` described by its header, ordered by offset.
+    ///
+    /// Empty sections are left out. The files listed in the file allocation table, overlays
+    /// included, are reported as a single section spanning all of them. The size of the banner
+    /// depends on its version, which is read from the banner itself.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading the file allocation table or the banner fails.
+    pub fn layout(&mut self) -> Result<Vec<Section>> {
+        let h = &self.header;
+        let span = |offset: u32, size: u32| u64::from(offset)..u64::from(offset) + u64::from(size);
+        let mut sections = vec![
+            Section::new("header", 0..h.header_size.into()),
+            Section::new("ARM9 binary", span(h.arm9_rom_offset, h.arm9_size)),
+            Section::new("ARM7 binary", span(h.arm7_rom_offset, h.arm7_size)),
+            Section::new("file name table", span(h.fnt_offset, h.fnt_size)),
+            Section::new("file allocation table", span(h.fat_offset, h.fat_size)),
+            Section::new(
+                "ARM9 overlay table",
+                span(h.arm9_overlay_offset, h.arm9_overlay_size),
+            ),
+            Section::new(
+                "ARM7 overlay table",
+                span(h.arm7_overlay_offset, h.arm7_overlay_size),
+            ),
+        ];
+
+        let banner_offset = h.banner_offset;
+        if banner_offset != 0 {
+            let version = Self::read_banner_version(&mut self.handle, self.base, banner_offset)?;
+            sections.push(Section::new(
+                "banner",
+                span(banner_offset, banner_size(version)),
+            ));
+        }
+        let files = Self::find_fat_range(&mut self.handle, &self.header, self.base)?;
+        sections.push(Section::new("files", files));
+        if let Some(cert) = &self.cert {
+            sections.push(Section::new("RSA certificate", cert.clone()));
+        }
+
+        sections.retain(|s| !s.range.is_empty());
+        sections.sort_by_key(|s| (s.range.start, s.range.end));
+
+        Ok(sections)
+    }
+
+    /// Reads the version of the banner at `offset`, relative to `base`.
+    fn read_banner_version(handle: &mut H, base: u64, offset: u32) -> io::Result<u16> {
+        let mut version = [0; 2];
+        handle.seek(SeekFrom::Start(base + u64::from(offset)))?;
+        handle.read_exact(&mut version)?;
+        Ok(u16::from_le_bytes(version))
+    }
+
+    /// Computes the end of the banner, or 0 if there's none or it extends past `file_size`.
+    fn find_banner_end(
+        handle: &mut H,
+        header: &NtrTwlHeader,
+        base: u64,
+        file_size: u64,
+    ) -> Result<u64> {
+        let offset = header.banner_offset;
+        if offset == 0 {
+            return Ok(0);
+        }
+
+        let version = match Self::read_banner_version(handle, base, offset) {
+            Ok(version) => version,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let end = u64::from(offset) + u64::from(banner_size(version));
+        Ok(if end <= file_size { end } else { 0 })
+    }
+
+    /// Reads the game titles stored in `self`'s banner, one per language.
+    ///
+    /// Unlike the 12-character internal title, these are the full names shown by the system menu,
+    /// usually spanning several lines, e.g. the title, a subtitle and the publisher. They're
+    /// ordered as Japanese, English, French, German, Italian and Spanish, followed by Chinese
+    /// and Korean in banners of later versions. Returns an empty list if `self` has no banner.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails, e.g. if the banner extends past the end of the file.
+    pub fn banner_titles(&mut self) -> Result<Vec<String>> {
+        const TITLES_OFFSET: usize = 0x240;
+        const TITLE_SIZE: usize = 0x100;
+
+        let offset = self.header.banner_offset;
+        if offset == 0 {
+            return Ok(Vec::new());
+        }
+        let version = Self::read_banner_version(&mut self.handle, self.base, offset)?;
+        let languages = match version {
+            0x0002 => 7,
+            0x0003 | 0x0103 => 8,
+            _ => 6,
+        };
+
+        let mut buf = vec![0; TITLE_SIZE * languages];
+        self.handle.seek(SeekFrom::Start(
+            self.base + u64::from(offset) + TITLES_OFFSET as u64,
+        ))?;
+        self.handle.read_exact(&mut buf)?;
+
+        Ok(buf
+            .chunks_exact(TITLE_SIZE)
+            .map(|title| {
+                let units: Vec<_> = title
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .take_while(|&u| u != 0)
+                    .collect();
+                String::from_utf16_lossy(&units)
+            })
+            .collect())
+    }
+
+    /// Checks whether `self`'s banner matches the CRC-16s stored in it.
+    ///
+    /// The banner holds the icon and titles shown by the system menu. Each banner version adds a
+    /// CRC covering the data it introduced, and all of them are checked. Returns `None` if `self`
+    /// has no banner, or if its version is unknown.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails, e.g. if the banner extends past the end of the file.
+    pub fn banner_crc_valid(&mut self) -> Result<Option<bool>> {
+        // The ranges covered by each CRC, relative to the banner, in the order they're stored.
+        const RANGES: [Range<usize>; 4] = [0x20..0x840, 0x20..0x940, 0x20..0xa40, 0x1240..0x23c0];
+
+        let offset = self.header.banner_offset;
+        if offset == 0 {
+            return Ok(None);
+        }
+        let version = Self::read_banner_version(&mut self.handle, self.base, offset)?;
+        let count = match version {
+            0x0001 => 1,
+            0x0002 => 2,
+            0x0003 => 3,
+            0x0103 => 4,
+            _ => return Ok(None),
+        };
+
+        let mut buf = vec![0; banner_size(version) as usize];
+        self.handle
+            .seek(SeekFrom::Start(self.base + u64::from(offset)))?;
+        self.handle.read_exact(&mut buf)?;
+
+        Ok(Some(RANGES[..count].iter().enumerate().all(|(i, range)| {
+            let stored = u16::from_le_bytes([buf[2 + 2 * i], buf[3 + 2 * i]]);
+            crc::checksum(&buf[range.clone()]) == stored
+        })))
+    }
+
+    /// Measures the zero bytes some dumpers append past the capacity of the cartridge.
+    ///
+    /// Returns the amount of such bytes, or `None` if the file doesn't extend past
+    /// [`NdsFile::device_capacity`], if the capacity is too small to hold the trimmed data, or if
+    /// the data past it isn't all zeros, e.g. in multi-ROM images.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn over_padding(&mut self) -> Result<Option<u64>> {
+        let capacity = self.device_capacity();
+        if self.file_size <= capacity || capacity < self.trimmed_size {
+            return Ok(None);
+        }
+
+        let mut buf = vec![0; self.buffer_size];
+        self.handle.seek(SeekFrom::Start(self.base + capacity))?;
+        let mut tail = Read::by_ref(&mut self.handle).take(self.file_size - capacity);
+        loop {
+            let n = read_full(&mut tail, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            if buf[..n].iter().any(|&b| b != 0) {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(self.file_size - capacity))
+    }
+
+    /// Seeks to the start of the ROM and returns a reader over its trimmed data.
+    ///
+    /// The reader yields exactly the bytes that [`NdsFile::trim_with_name`] would write, so they
+    /// can be hashed or streamed elsewhere without writing a file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if seeking to the start of the ROM fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io;
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let src = PathBuf::from("foo.nds");
+    /// let mut ndsfile = NdsFile::options().read_only(true).open(&src)?;
+    ///
+    /// let copied = io::copy(&mut ndsfile.trimmed_reader()?, &mut io::sink())?;
+    /// assert_eq!(copied, ndsfile.trimmed_size());
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn trimmed_reader(&mut self) -> Result<io::Take<&mut H>> {
+        self.handle.seek(SeekFrom::Start(self.base))?;
+        Ok(Read::by_ref(&mut self.handle).take(self.trimmed_size))
+    }
+
+    /// Consumes `self`, returning its trimmed data.
+    ///
+    /// This allows handing the data to a compressor or an uploader without writing a file. The
+    /// whole trimmed data is held in memory.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the trimmed data doesn't fit in memory, or if reading it fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let ndsfile = NdsFile::open_from_bytes(fs::read("foo.nds")?)?;
+    /// let trimmed = ndsfile.into_trimmed_bytes()?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn into_trimmed_bytes(mut self) -> Result<Vec<u8>> {
+        let size = usize::try_from(self.trimmed_size)
+            .map_err(|e| io::Error::new(ErrorKind::OutOfMemory, e))?;
+        let mut data = vec![0; size];
+        self.trimmed_reader()?.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// Returns a reference to the underlying handle.
+    pub fn get_ref(&self) -> &H {
+        &self.handle
+    }
+
+    /// Consumes `self`, returning the underlying handle.
+    ///
+    /// The position of the handle is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use ndstrim::nds::NdsFile;
+    ///
+    /// let path = PathBuf::from("foo.nds");
+    /// let mut ndsfile = NdsFile::open(&path)?;
+    ///
+    /// ndsfile.trim()?;
+    /// let file = ndsfile.into_inner();
+    /// file.sync_all()?;
+    /// # Ok::<(), ndstrim::nds::Error>(())
+    /// ```
+    pub fn into_inner(self) -> H {
+        self.handle
+    }
+
+    /// Overrides the trimmed size of `self` with `size`, e.g. a known-good size for a ROM whose
+    /// header is wrong.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::InvalidTrimSize`] if `size` is smaller than the size declared in the
+    /// header, or if it isn't smaller than the file.
+    pub fn trim_to(&mut self, size: u64) -> Result<()> {
+        let minimum = self.used_size();
+        if size < minimum || size >= self.file_size {
+            return Err(Error::InvalidTrimSize {
+                size,
+                minimum,
+                maximum: self.file_size,
+            });
+        }
+
+        self.trimmed_size = size;
+        self.reason = TrimReason::Override;
         Ok(())
     }
 
+    /// Checks whether `self`'s secure area matches the CRC-16 stored in the header.
+    ///
+    /// The CRC covers the secure area as stored on the cartridge, which is encrypted, so it can't
+    /// be checked in dumps whose secure area was decrypted or that have none, e.g. homebrew. In
+    /// such cases, `None` is returned.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn secure_area_crc_valid(&mut self) -> Result<Option<bool>> {
+        const START: u64 = 0x4000;
+        const END: u64 = 0x8000;
+        // Decrypted secure areas start with either the decrypted ID or its destroyed form.
+        const DECRYPTED: [[u8; 8]; 2] = [
+            *b"encryObj",
+            [0xff, 0xde, 0xff, 0xe7, 0xff, 0xde, 0xff, 0xe7],
+        ];
+
+        let start = u64::from(self.header.arm9_rom_offset);
+        if !(START..END).contains(&start) || self.trimmed_size < END {
+            return Ok(None);
+        }
+
+        let mut buf = vec![0; usize::try_from(END - start).unwrap_or_default()];
+        self.handle.seek(SeekFrom::Start(self.base + start))?;
+        self.handle.read_exact(&mut buf)?;
+        if buf.iter().all(|&b| b == 0) || DECRYPTED.iter().any(|id| buf.starts_with(id)) {
+            return Ok(None);
+        }
+
+        Ok(Some(crc::checksum(&buf) == self.header.secure_area_crc))
+    }
+
+    /// Checks whether the CRC stored in `self`'s header matches the header.
+    ///
+    /// This is only `false` for ROMs opened with [`OpenOptions::accept_stale_crc`].
+    pub fn header_crc_valid(&self) -> bool {
+        self.header.header_crc == self.computed_header_crc
+    }
+
+    /// Replaces the CRC stored in `self`'s header with the one computed over the header.
+    ///
+    /// # Errors
+    ///
+    /// Fails if writing to `self` fails, e.g. because it was opened read-only.
+    pub fn fix_header_crc(&mut self) -> Result<()> {
+        const OFFSET: u64 = 0x15e;
+
+        self.handle
+            .write_all_at(self.base + OFFSET, &self.computed_header_crc.to_le_bytes())?;
+        self.header.header_crc = self.computed_header_crc;
+        Ok(())
+    }
+
+    /// Computes the CRC-32 of `self`'s raw header, which identifies a ROM before it's trimmed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading from `self` fails.
+    pub fn header_crc32(&mut self) -> Result<u32> {
+        let mut buf = vec![0; mem::size_of::<NtrTwlHeader>()];
+        self.handle.seek(SeekFrom::Start(self.base))?;
+        self.handle.read_exact(&mut buf)?;
+
+        let mut crc = crc::Crc32::new();
+        crc.update(&buf);
+        Ok(crc.finalize())
+    }
+
+    /// Sets the size of the buffers used to copy and compare data.
+    ///
+    /// Sizes smaller than [`MIN_BUFFER_SIZE`] are rounded up to it.
+    pub fn set_buffer_size(&mut self, size: usize) {
+        self.buffer_size = size.max(MIN_BUFFER_SIZE);
+    }
+
+    /// Sets whether copies are written as sparse files.
+    ///
+    /// If `true`, chunks of [`NdsFile::set_buffer_size`] bytes that are all zeros are skipped
+    /// rather than written, leaving holes on filesystems that support them, e.g. ext4, Btrfs, XFS
+    /// and APFS. Others, such as NTFS on Windows, store the zeros anyway. The copied data reads
+    /// back identically either way.
+    pub fn set_sparse(&mut self, sparse: bool) {
+        self.sparse = sparse;
+    }
+
     /// Returns `self`'s on-disk file size.
+    ///
+    /// If `self` doesn't start at the beginning of the file, this is the amount of bytes from the
+    /// start of the ROM to the end of the file.
     pub fn file_size(&self) -> u64 {
         self.file_size
     }
@@ -232,4 +2074,856 @@ impl NdsFile {
     pub fn trimmed_size(&self) -> u64 {
         self.trimmed_size
     }
+
+    /// Returns `self`'s four-character game code, e.g. `AMCE`.
+    pub fn gamecode(&self) -> String {
+        String::from_utf8_lossy(&self.header.gamecode).into_owned()
+    }
+
+    /// Returns an identifier for `self` made of its game code, maker code and ROM version, e.g.
+    /// `AMCE-01-v0`.
+    ///
+    /// Unlike the game code alone, this tells revisions of the same game apart, so it can be used
+    /// as a catalog key. Bytes that aren't printable ASCII are replaced by `_`.
+    pub fn serial(&self) -> String {
+        let printable = |b: &u8| {
+            if b.is_ascii_graphic() {
+                char::from(*b)
+            } else {
+                '_'
+            }
+        };
+        let gamecode: String = self.header.gamecode.iter().map(printable).collect();
+        let makercode: String = self.header.makercode.iter().map(printable).collect();
+
+        format!("{gamecode}-{makercode}-v{}", self.header.rom_version)
+    }
+
+    /// Returns `self`'s internal title, e.g. `MARIOKARTDS`.
+    pub fn title(&self) -> String {
+        String::from_utf8_lossy(&self.header.title)
+            .trim_end_matches('\0')
+            .to_owned()
+    }
+
+    /// Returns the name of the region `self` was released in, derived from its game code.
+    pub fn region(&self) -> &'static str {
+        match self.header.gamecode[3] {
+            b'A' => "Asia",
+            b'C' => "China",
+            b'D' => "Germany",
+            b'E' | b'L' => "USA",
+            b'F' => "France",
+            b'H' => "Netherlands",
+            b'I' => "Italy",
+            b'J' => "Japan",
+            b'K' => "Korea",
+            b'M' => "Sweden",
+            b'N' => "Norway",
+            b'O' => "International",
+            b'P' | b'W'..=b'Z' => "Europe",
+            b'Q' => "Denmark",
+            b'R' => "Russia",
+            b'S' => "Spain",
+            b'T' => "USA/Australia",
+            b'U' => "Australia",
+            b'V' => "Europe/Australia",
+            _ => "Unknown",
+        }
+    }
+
+    /// Checks whether `self` is DSiWare or another title installed to the DSi's system memory.
+    ///
+    /// Such titles aren't padded like cartridge dumps, so trimming them is pointless at best.
+    pub fn is_dsiware(&self) -> bool {
+        self.header.is_dsiware()
+    }
+
+    /// Returns `self`'s TWL title ID, or `None` if `self` is NTR-only.
+    ///
+    /// The high half identifies the kind of title, e.g. `0x00030000` for cartridges.
+    pub fn title_id(&self) -> Option<u64> {
+        (!self.header.is_ntr_only()).then_some(self.header.title_id)
+    }
+
+    /// Checks whether `self` is homebrew rather than a retail dump.
+    ///
+    /// Homebrew sizes aren't rounded to cartridge sizes, and its Nintendo logo may be invalid,
+    /// which requires opening it with [`OpenOptions::lenient`].
+    pub fn is_homebrew(&self) -> bool {
+        self.header.is_homebrew()
+    }
+
+    /// Checks whether `self`'s Nintendo logo is byte-for-byte identical to the canonical one.
+    ///
+    /// This is stricter than the CRC check done on open, which a forged logo may pass.
+    pub fn logo_matches_canonical(&self) -> bool {
+        self.header.logo_matches_canonical()
+    }
+
+    /// Returns the inconsistency between `self`'s header and its on-disk size, if any.
+    ///
+    /// Files whose header declares more data than they contain fail to open with
+    /// [`Error::Truncated`] instead.
+    pub fn size_warning(&self) -> Option<SizeWarning> {
+        self.size_warning
+    }
+
+    /// Returns what determined `self`'s trimmed size.
+    ///
+    /// Several constraints may extend the size declared in the header, and the one reported is
+    /// the last to extend it.
+    pub fn trim_reason(&self) -> TrimReason {
+        self.reason
+    }
+
+    /// Returns the end of the last file in `self`'s file allocation table.
+    ///
+    /// This is only available if `self` was opened with [`OpenOptions::tight`].
+    pub fn fat_end(&self) -> Option<u64> {
+        self.fat_end
+    }
+
+    /// Returns the end of the last overlay listed in `self`'s ARM9 and ARM7 overlay tables, or 0
+    /// if there are none.
+    ///
+    /// The trimmed size never falls short of it, even if the header declares a smaller size, as
+    /// games load overlays on demand and would crash on a missing one.
+    pub fn overlays_end(&self) -> u64 {
+        self.overlays_end
+    }
+
+    /// Returns the byte range of the RSA certificate needed by Download Play, relative to the
+    /// start of the ROM, or `None` if `self` has none.
+    ///
+    /// This only covers the 0x88-byte certificate that follows the ROM data declared in the
+    /// header, not the data Download Play sends to other consoles. The certificate is usually
+    /// right after the ROM data, but some dumps pad it to a further aligned offset. Only NTR ROMs
+    /// carry one. It's part of the trimmed data unless it was discarded with
+    /// [`OpenOptions::preserve_cert`].
+    pub fn cert_range(&self) -> Option<Range<u64>> {
+        self.cert.clone()
+    }
+
+    /// Returns the size of the ROM data declared in `self`'s header.
+    ///
+    /// Unlike [`NdsFile::trimmed_size`], this never includes the RSA certificate.
+    pub fn used_size(&self) -> u64 {
+        self.header.used_size()
+    }
+
+    /// Checks whether the size declared in `self`'s header is a multiple of `alignment` bytes.
+    ///
+    /// Well-formed dumps are aligned to the cartridge's read granularity, so an odd size hints at
+    /// a bad dump.
+    pub fn is_aligned(&self, alignment: u64) -> bool {
+        self.used_size().is_multiple_of(alignment)
+    }
+
+    /// Returns the capacity of the cartridge `self` was dumped from.
+    ///
+    /// This is always a power of two, computed as 128 KiB shifted left by the header's device
+    /// capacity code.
+    pub fn device_capacity(&self) -> u64 {
+        self.header.device_capacity()
+    }
+
+    /// Returns the size of `self` before it was trimmed, as dumped from its cartridge.
+    ///
+    /// This is the device capacity, unless the header declares one too small to hold the trimmed
+    /// data, in which case the trimmed size rounded up to a power of two is returned.
+    pub fn full_size(&self) -> u64 {
+        let capacity = self.device_capacity();
+        if capacity >= self.trimmed_size {
+            capacity
+        } else {
+            self.trimmed_size.next_power_of_two()
+        }
+    }
+}
+
+/// Searches the first `limit` bytes of `reader` for a header, returning its offset.
+///
+/// This is meant to salvage dumps with garbage before the header, which is only recognized by its
+/// Nintendo logo CRC and header CRC. Pass the offset to [`OpenOptions::offset`] to open the ROM.
+///
+/// # Errors
+///
+/// Fails if reading from `reader` fails.
+pub fn find_header<R: Read + Seek>(reader: &mut R, limit: u64) -> Result<Option<u64>> {
+    const LOGO_CRC: usize = 0x15c;
+    const HEADER_CRC: usize = 0x15e;
+
+    reader.seek(SeekFrom::Start(0))?;
+    let mut buf = Vec::new();
+    reader
+        .take(limit + HEADER_CRC as u64 + 1)
+        .read_to_end(&mut buf)?;
+
+    let offset = buf.windows(HEADER_CRC + 2).position(|w| {
+        let stored = |i: usize| u16::from_le_bytes([w[i], w[i + 1]]);
+        stored(LOGO_CRC) == 0xcf56 && stored(HEADER_CRC) == crc::checksum(&w[..HEADER_CRC])
+    });
+
+    Ok(offset.map(|o| o as u64))
+}
+
+/// Runs each of the checks done on a header separately, on the header at the current position of
+/// `reader`.
+///
+/// Unlike [`OpenOptions::open`], this doesn't stop at the first failing check, which helps tell
+/// patched ROMs from corrupt ones.
+///
+/// # Errors
+///
+/// Fails if reading the header from `reader` fails, e.g. if it's too short to hold one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use ndstrim::nds;
+///
+/// let checks = nds::check_header(&mut File::open("foo.nds")?)?;
+/// if checks.likely_patched() {
+///     println!("patched header");
+/// }
+/// # Ok::<(), ndstrim::nds::Error>(())
+/// ```
+pub fn check_header<R: Read>(reader: &mut R) -> Result<HeaderChecks> {
+    let mut buf = vec![0; mem::size_of::<NtrTwlHeader>()];
+    reader.read_exact(&mut buf)?;
+
+    Ok(NtrTwlHeader::check(&buf)?.1)
+}
+
+/// Estimates how much trimming the ROMs at `paths` would save, without modifying them.
+///
+/// Already trimmed ROMs contribute no savings, while paths that can't be opened as ROMs are
+/// recorded as skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use ndstrim::nds;
+///
+/// let paths = [PathBuf::from("foo.nds"), PathBuf::from("bar.nds")];
+/// let report = nds::estimate_savings(&paths);
+/// println!("{} bytes could be saved", report.saved());
+/// ```
+pub fn estimate_savings(paths: &[PathBuf]) -> SavingsReport {
+    let mut options = OpenOptions::new();
+    options.read_only(true);
+
+    let mut report = SavingsReport::default();
+    for path in paths {
+        let sizes = match options.open(path) {
+            Ok(f) => Ok((f.file_size(), f.trimmed_size())),
+            Err(Error::AlreadyTrimmed) => fs::metadata(path)
+                .map(|m| (m.len(), m.len()))
+                .map_err(Error::from),
+            Err(e) => Err(e),
+        };
+        match sizes {
+            Ok((file_size, trimmed_size)) => report.entries.push(Savings {
+                path: path.clone(),
+                file_size,
+                trimmed_size,
+            }),
+            Err(e) => report.skipped.push((path.clone(), e)),
+        }
+    }
+
+    report
+}
+
+/// Returns the size of banners of `version`.
+///
+/// Unknown versions are assumed to be as large as the original one.
+fn banner_size(version: u16) -> u32 {
+    match version {
+        0x0002 => 0x940,
+        0x0003 => 0xa40,
+        0x0103 => 0x23c0,
+        _ => 0x840,
+    }
+}
+
+/// Reads from `reader` until `buf` is full or EOF is reached, returning the amount of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match reader.read(&mut buf[total..]) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::env;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::fixture::build_rom;
+
+    /// Returns an empty directory for the test `name`, removing what earlier runs left in it.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ndstrim-test-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn atomic_trim_leaves_no_temporary_file() {
+        let dir = test_dir("atomic");
+        let dest = dir.join("out.nds");
+        let mut ndsfile = NdsFile::open_from_bytes(build_rom(0x8000, 0x20000)).unwrap();
+
+        ndsfile.trim_with_name_atomic(&dest).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), build_rom(0x8000, 0x8000));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn atomic_trim_removes_temporary_file_on_failure() {
+        let dir = test_dir("atomic-failure");
+        // Renaming a file over a directory fails.
+        let dest = dir.join("out.nds");
+        fs::create_dir(&dest).unwrap();
+        let mut ndsfile = NdsFile::open_from_bytes(build_rom(0x8000, 0x20000)).unwrap();
+
+        assert!(ndsfile.trim_with_name_atomic(&dest).is_err());
+
+        assert!(dest.is_dir());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Builds a ROM like [`build_rom`], with its header edited by `edit` and its CRC fixed up.
+    fn build_rom_with(rom_size: u32, file_size: usize, edit: impl FnOnce(&mut [u8])) -> Vec<u8> {
+        let mut rom = build_rom(rom_size, file_size);
+        edit(&mut rom[..0x1000]);
+        let header_crc = crc::checksum(&rom[..0x15e]);
+        rom[0x15e..0x160].copy_from_slice(&header_crc.to_le_bytes());
+        rom
+    }
+
+    /// Builds a ROM declaring 0x9000 bytes, with a FAT at 0x2000 listing `files` as start and end
+    /// offsets, and ARM9 and ARM7 overlay tables at 0x2100 and 0x2200 listing the file IDs in
+    /// `arm9` and `arm7`.
+    fn build_rom_with_files(files: &[(u32, u32)], arm9: &[u32], arm7: &[u32]) -> Vec<u8> {
+        let size = |entries: usize, entry_size: usize| u32::try_from(entries * entry_size).unwrap();
+        let mut rom = build_rom_with(0x9000, 0x20000, |h| {
+            h[0x48..0x4c].copy_from_slice(&0x2000_u32.to_le_bytes());
+            h[0x4c..0x50].copy_from_slice(&size(files.len(), 8).to_le_bytes());
+            h[0x50..0x54].copy_from_slice(&0x2100_u32.to_le_bytes());
+            h[0x54..0x58].copy_from_slice(&size(arm9.len(), 32).to_le_bytes());
+            h[0x58..0x5c].copy_from_slice(&0x2200_u32.to_le_bytes());
+            h[0x5c..0x60].copy_from_slice(&size(arm7.len(), 32).to_le_bytes());
+        });
+
+        for (i, &(start, end)) in files.iter().enumerate() {
+            let entry = 0x2000 + i * 8;
+            rom[entry..entry + 4].copy_from_slice(&start.to_le_bytes());
+            rom[entry + 4..entry + 8].copy_from_slice(&end.to_le_bytes());
+        }
+        for (table, ids) in [(0x2100, arm9), (0x2200, arm7)] {
+            for (i, id) in ids.iter().enumerate() {
+                let entry = table + i * 32;
+                rom[entry..entry + 32].fill(0);
+                rom[entry + 0x18..entry + 0x1c].copy_from_slice(&id.to_le_bytes());
+            }
+        }
+        rom
+    }
+
+    #[test]
+    fn device_capacity_follows_capacity_code() {
+        for (code, capacity) in [(0, 0x2_0000), (7, 0x100_0000), (9, 0x400_0000)] {
+            let rom = build_rom_with(0x8000, 0x20000, |h| h[0x14] = code);
+            let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+            assert_eq!(ndsfile.device_capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn used_size_is_declared_size() {
+        let ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+        assert_eq!(ndsfile.used_size(), 0x9000);
+        assert_eq!(ndsfile.file_size(), 0x20000);
+    }
+
+    #[test]
+    fn progress_reports_every_chunk_up_to_trimmed_size() {
+        let dir = test_dir("progress");
+        let dest = dir.join("out.nds");
+        let mut ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+        ndsfile.set_buffer_size(MIN_BUFFER_SIZE);
+
+        let mut calls = Vec::new();
+        ndsfile
+            .trim_with_name_progress(&dest, |copied, total| calls.push((copied, total)))
+            .unwrap();
+
+        assert_eq!(calls.len(), 9);
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(calls.iter().all(|&(_, total)| total == 0x9000));
+        assert_eq!(calls.last(), Some(&(0x9000, 0x9000)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn byte_swapped_dump_is_recognized() {
+        let mut rom = build_rom(0x8000, 0x20000);
+        for pair in rom.chunks_exact_mut(2) {
+            pair.swap(0, 1);
+        }
+
+        assert!(matches!(
+            NdsFile::open_from_bytes(rom),
+            Err(Error::ByteSwapped)
+        ));
+    }
+
+    #[test]
+    fn too_small_file_is_reported() {
+        for len in [0, 10] {
+            assert!(matches!(
+                NdsFile::open_from_bytes(vec![0xff; len]),
+                Err(Error::TooSmall { actual, needed: 0x1000 }) if actual == len as u64
+            ));
+        }
+    }
+
+    #[test]
+    fn full_size_is_capacity_or_next_power_of_two() {
+        // The capacity code declares 128 KiB, 512 KiB and 1 MiB respectively.
+        for (code, rom_size, full_size) in [
+            (0, 0x8000, 0x2_0000),
+            (2, 0x2_1000, 0x8_0000),
+            (3, 0x2_1000, 0x10_0000),
+        ] {
+            let rom = build_rom_with(rom_size, 0x20_0000, |h| h[0x14] = code);
+            let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+            assert_eq!(ndsfile.full_size(), full_size);
+        }
+
+        // A capacity too small for the data is disregarded.
+        let rom = build_rom_with(0x3_1000, 0x20_0000, |h| h[0x14] = 0);
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.full_size(), 0x4_0000);
+    }
+
+    #[test]
+    fn short_file_is_truncated_not_trimmed() {
+        let mut rom = build_rom(0x8000, 0x20000);
+        rom.truncate(0x6000);
+        assert!(matches!(
+            NdsFile::open_from_bytes(rom),
+            Err(Error::Truncated { actual: 0x6000, expected: 0x8000 })
+        ));
+
+        assert!(matches!(
+            NdsFile::open_from_bytes(build_rom(0x8000, 0x8000)),
+            Err(Error::AlreadyTrimmed)
+        ));
+    }
+
+    #[test]
+    fn trimmed_reader_yields_trimmed_size_bytes() {
+        let mut ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+
+        let mut data = Vec::new();
+        ndsfile.trimmed_reader().unwrap().read_to_end(&mut data).unwrap();
+
+        assert_eq!(data.len() as u64, ndsfile.trimmed_size());
+        assert_eq!(data, build_rom(0x9000, 0x9000));
+    }
+
+    #[test]
+    fn stale_crc_with_valid_logo_is_told_apart() {
+        let mut rom = build_rom(0x8000, 0x20000);
+        rom[0] = b'X';
+        let stored = u16::from_le_bytes([rom[0x15e], rom[0x15f]]);
+        let computed = crc::checksum(&rom[..0x15e]);
+
+        assert!(matches!(
+            NdsFile::open_from_bytes(rom.clone()),
+            Err(Error::StaleHeaderCrc { stored: s, computed: c }) if s == stored && c == computed
+        ));
+
+        let ndsfile = OpenOptions::new()
+            .accept_stale_crc(true)
+            .open_handle(Cursor::new(rom))
+            .unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x8000);
+    }
+
+    #[test]
+    fn corrupt_header_is_bad() {
+        let mut rom = build_rom(0x8000, 0x20000);
+        // Both the logo and the header no longer match their CRCs.
+        rom[0xc0..0x15c].fill(0);
+
+        assert!(matches!(
+            OpenOptions::new()
+                .accept_stale_crc(true)
+                .open_handle(Cursor::new(rom)),
+            Err(Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn serial_joins_codes_and_version() {
+        let ndsfile = NdsFile::open_from_bytes(build_rom(0x8000, 0x20000)).unwrap();
+        assert_eq!(ndsfile.serial(), "NTRE-01-v0");
+
+        let rom = build_rom_with(0x8000, 0x20000, |h| {
+            h[0x0d] = 0;
+            h[0x1e] = 3;
+        });
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.serial(), "N_RE-01-v3");
+    }
+
+    #[test]
+    fn cert_is_kept_even_if_damaged() {
+        for magic in [*b"ac", [0x5a; 2]] {
+            let mut rom = build_rom(0x9000, 0x20000);
+            rom[0x9000..0x9088].fill(0x5a);
+            rom[0x9000..0x9002].copy_from_slice(&magic);
+            let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+            assert_eq!(ndsfile.trimmed_size(), 0x9088);
+        }
+    }
+
+    #[test]
+    fn padding_is_no_cert() {
+        let ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x9000);
+
+        let mut rom = build_rom(0x9000, 0x20000);
+        rom[0x9000..0x9088].fill(0);
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x9000);
+    }
+
+    #[test]
+    fn cert_range_follows_rom_data() {
+        let mut rom = build_rom(0x9000, 0x20000);
+        rom[0x9000..0x9088].fill(0x5a);
+        rom[0x9000..0x9002].copy_from_slice(b"ac");
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.cert_range(), Some(0x9000..0x9088));
+
+        let mut rom = build_rom(0x9000, 0x20000);
+        rom[0x9000..0x9040].fill(0xff);
+        rom[0x9040..0x90c8].fill(0x5a);
+        rom[0x9040..0x9042].copy_from_slice(b"ac");
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.cert_range(), Some(0x9040..0x90c8));
+
+        let ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+        assert_eq!(ndsfile.cert_range(), None);
+    }
+
+    #[test]
+    fn data_at_rom_boundary_is_no_cert_in_multi_rom_files() {
+        // Data right after a ROM ending on the multi-ROM spacing may be the next ROM.
+        let mut rom = build_rom(0x2_0000, 0x4_0000);
+        rom[0x2_0000..0x2_0088].fill(0x5a);
+        let ndsfile = NdsFile::options().multi_rom(true).open_handle(Cursor::new(rom.clone()));
+        assert_eq!(ndsfile.unwrap().trimmed_size(), 0x2_0000);
+
+        // Otherwise, it's still taken for a damaged certificate.
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x2_0088);
+    }
+
+    #[test]
+    fn overlays_within_declared_size_keep_it() {
+        let rom = build_rom_with_files(&[(0x3000, 0x4000), (0x4000, 0x6000)], &[0], &[1]);
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.overlays_end(), 0x6000);
+        assert_eq!(ndsfile.trimmed_size(), 0x9000);
+        assert_eq!(ndsfile.trim_reason(), TrimReason::NtrSize);
+    }
+
+    #[test]
+    fn overlays_past_declared_size_extend_it() {
+        let files = [(0x3000, 0x4000), (0x9000, 0xa000), (0xa000, 0xb000)];
+        for (arm9, arm7, end) in [(&[1][..], &[][..], 0xa000), (&[0], &[2], 0xb000)] {
+            let rom = build_rom_with_files(&files, arm9, arm7);
+            let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+            assert_eq!(ndsfile.overlays_end(), end);
+            assert_eq!(ndsfile.trimmed_size(), end);
+            assert_eq!(ndsfile.trim_reason(), TrimReason::Overlays);
+        }
+    }
+
+    #[test]
+    fn empty_overlay_tables_end_at_zero() {
+        let rom = build_rom_with_files(&[(0x3000, 0xa000)], &[], &[]);
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.overlays_end(), 0);
+        assert_eq!(ndsfile.trimmed_size(), 0x9000);
+
+        // IDs outside the FAT can't be resolved, so they're skipped too.
+        let rom = build_rom_with_files(&[(0x3000, 0xa000)], &[1], &[7]);
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.overlays_end(), 0);
+    }
+
+    #[test]
+    fn fat_end_skips_unused_entries() {
+        let files = [(0x3000, 0x4000), (0, 0), (0xc000, 0xb000), (0x4000, 0x7000)];
+        let rom = build_rom_with_files(&files, &[], &[]);
+        let ndsfile = NdsFile::options().tight(true).open_handle(Cursor::new(rom)).unwrap();
+        assert_eq!(ndsfile.fat_end(), Some(0x7000));
+        assert_eq!(ndsfile.trimmed_size(), 0x9000);
+        assert_eq!(ndsfile.trim_reason(), TrimReason::NtrSize);
+
+        let ndsfile = NdsFile::open_from_bytes(build_rom_with_files(&files, &[], &[])).unwrap();
+        assert_eq!(ndsfile.fat_end(), None);
+    }
+
+    #[test]
+    fn fat_end_past_declared_size_extends_it_when_tight() {
+        let rom = build_rom_with_files(&[(0x3000, 0x4000), (0x9000, 0xa000)], &[], &[]);
+        let ndsfile = NdsFile::options().tight(true).open_handle(Cursor::new(rom.clone())).unwrap();
+        assert_eq!(ndsfile.fat_end(), Some(0xa000));
+        assert_eq!(ndsfile.trimmed_size(), 0xa000);
+        assert_eq!(ndsfile.trim_reason(), TrimReason::FatEnd);
+
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x9000);
+    }
+
+    #[test]
+    fn bytes_round_trip_through_trimming() {
+        let ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+        let trimmed = ndsfile.into_trimmed_bytes().unwrap();
+        assert_eq!(trimmed, build_rom(0x9000, 0x9000));
+
+        assert!(matches!(
+            NdsFile::open_from_bytes(trimmed),
+            Err(Error::AlreadyTrimmed)
+        ));
+    }
+
+    #[test]
+    fn shifted_cert_is_kept_whole() {
+        for padding in [0x00, 0xff] {
+            let mut rom = build_rom(0x9000, 0x20000);
+            rom[0x9000..0x9040].fill(padding);
+            rom[0x9040..0x90c8].fill(0x5a);
+            rom[0x9040..0x9042].copy_from_slice(b"ac");
+            let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+            assert_eq!(ndsfile.trimmed_size(), 0x90c8);
+        }
+
+        // Unaligned magic isn't followed, so a damaged certificate is assumed at the ROM's end.
+        let mut rom = build_rom(0x9000, 0x20000);
+        rom[0x9042..0x9044].copy_from_slice(b"ac");
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x9088);
+    }
+
+    #[test]
+    fn rounding_finalizes_trimmed_size() {
+        let mut rom = build_rom(0x9000, 0x4_0000);
+        rom[0x9000..0x9088].fill(0x5a);
+        rom[0x9000..0x9002].copy_from_slice(b"ac");
+
+        for (rounding, trimmed_size) in [
+            (Rounding::Exact, 0x9000),
+            (Rounding::Cert, 0x9088),
+            (Rounding::Sector(0x200), 0x9200),
+            (Rounding::Sector(0), 0x9088),
+            (Rounding::Pow2, 0x2_0000),
+        ] {
+            let ndsfile = OpenOptions::new()
+                .rounding(rounding)
+                .open_handle(Cursor::new(rom.clone()))
+                .unwrap();
+            assert_eq!(ndsfile.trimmed_size(), trimmed_size, "{rounding:?}");
+        }
+
+        // Past a capacity too small for the data, the next power of two is used instead.
+        let ndsfile = OpenOptions::new()
+            .rounding(Rounding::Pow2)
+            .open_handle(Cursor::new(build_rom(0x3_1000, 0x8_0000)))
+            .unwrap();
+        assert_eq!(ndsfile.trimmed_size(), 0x4_0000);
+
+        assert!(matches!(
+            OpenOptions::new()
+                .rounding(Rounding::Pow2)
+                .open_handle(Cursor::new(build_rom(0x9000, 0x2_0000))),
+            Err(Error::AlreadyTrimmed)
+        ));
+    }
+
+    #[test]
+    fn title_id_is_only_read_from_twl_headers() {
+        let ndsfile = NdsFile::open_from_bytes(build_rom(0x8000, 0x20000)).unwrap();
+        assert_eq!(ndsfile.title_id(), None);
+
+        let rom = build_rom_with(0x8000, 0x20000, |h| {
+            h[0x12] = 0x02;
+            h[0x210..0x214].copy_from_slice(&0x8000_u32.to_le_bytes());
+            h[0x230..0x238].copy_from_slice(&0x0003_0000_4552_544e_u64.to_le_bytes());
+        });
+        let ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.title_id(), Some(0x0003_0000_4552_544e));
+        assert!(!ndsfile.is_dsiware());
+    }
+
+    /// A handle that fails to read past `fail_at` once `armed` is set.
+    struct Faulty {
+        data: Cursor<Vec<u8>>,
+        fail_at: u64,
+        armed: Rc<Cell<bool>>,
+    }
+
+    impl Read for Faulty {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.armed.get() && self.data.position() >= self.fail_at {
+                return Err(io::Error::other("bad sector"));
+            }
+            self.data.read(buf)
+        }
+    }
+
+    impl Seek for Faulty {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.data.seek(pos)
+        }
+    }
+
+    impl Handle for Faulty {
+        fn set_len(&mut self, _: u64) -> io::Result<()> {
+            Err(ErrorKind::Unsupported.into())
+        }
+
+        fn try_clone(&self) -> io::Result<Self> {
+            Ok(Self {
+                data: self.data.clone(),
+                fail_at: self.fail_at,
+                armed: Rc::clone(&self.armed),
+            })
+        }
+    }
+
+    #[test]
+    fn failed_copy_removes_destination() {
+        let dir = test_dir("failed-copy");
+        let dest = dir.join("out.nds");
+        let armed = Rc::new(Cell::new(false));
+        let handle = Faulty {
+            data: Cursor::new(build_rom(0x9000, 0x20000)),
+            fail_at: 0x4000,
+            armed: Rc::clone(&armed),
+        };
+        let mut ndsfile = OpenOptions::new().read_only(true).open_handle(handle).unwrap();
+        ndsfile.set_buffer_size(MIN_BUFFER_SIZE);
+        armed.set(true);
+
+        assert!(ndsfile.trim_with_name(&dest).is_err());
+
+        assert!(!dest.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sparse_copy_reads_back_identically() {
+        let dir = test_dir("sparse");
+        let dest = dir.join("out.nds");
+        let mut rom = build_rom(0x9000, 0x20000);
+        rom[0x2000..0x4000].fill(0);
+        rom[0x8000..0x9000].fill(0);
+        let mut ndsfile = NdsFile::open_from_bytes(rom.clone()).unwrap();
+        ndsfile.set_buffer_size(MIN_BUFFER_SIZE);
+        ndsfile.set_sparse(true);
+
+        ndsfile.trim_with_name(&dest).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), rom[..0x9000]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resume_continues_interrupted_copy() {
+        let dir = test_dir("resume");
+        let dest = dir.join("out.nds");
+        let rom = build_rom(0x9000, 0x20000);
+        let mut ndsfile = NdsFile::open_from_bytes(rom.clone()).unwrap();
+        ndsfile.trim_with_name(&dest).unwrap();
+        File::options().write(true).open(&dest).unwrap().set_len(0x5000).unwrap();
+
+        assert_eq!(ndsfile.resume_trim_with_name(&dest).unwrap(), 0x5000);
+        assert_eq!(fs::read(&dest).unwrap(), rom[..0x9000]);
+
+        // A complete copy is left as is.
+        assert_eq!(ndsfile.resume_trim_with_name(&dest).unwrap(), 0x9000);
+        assert_eq!(fs::read(&dest).unwrap(), rom[..0x9000]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn verified_resume_rewrites_from_first_difference() {
+        let dir = test_dir("resume-verified");
+        let dest = dir.join("out.nds");
+        let rom = build_rom(0x9000, 0x20000);
+        let mut partial = rom[..0x5000].to_vec();
+        partial[0x3000] ^= 0xff;
+        fs::write(&dest, partial).unwrap();
+        let mut ndsfile = NdsFile::open_from_bytes(rom.clone()).unwrap();
+
+        assert_eq!(ndsfile.resume_trim_with_name_verified(&dest).unwrap(), 0x3000);
+        assert_eq!(fs::read(&dest).unwrap(), rom[..0x9000]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn over_padding_is_measured_past_capacity() {
+        let mut rom = build_rom(0x8000, 0x30000);
+        rom[0x20000..].fill(0);
+        let mut ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.over_padding().unwrap(), Some(0x10000));
+
+        let mut ndsfile = NdsFile::open_from_bytes(build_rom(0x8000, 0x30000)).unwrap();
+        assert_eq!(ndsfile.over_padding().unwrap(), None);
+
+        // The capacity is too small for the data, so there's no padding past it.
+        let mut rom = build_rom(0x3_1000, 0x8_0000);
+        rom[0x4_0000..].fill(0);
+        let mut ndsfile = NdsFile::open_from_bytes(rom).unwrap();
+        assert_eq!(ndsfile.over_padding().unwrap(), None);
+    }
+
+    #[test]
+    fn fingerprint_is_crc_of_trimmed_file() {
+        let mut crc = crc::Crc32::new();
+        crc.update(&build_rom(0x9000, 0x9000));
+        let expected = crc.finalize();
+
+        let mut ndsfile = NdsFile::open_from_bytes(build_rom(0x9000, 0x20000)).unwrap();
+        assert_eq!(ndsfile.content_fingerprint().unwrap(), expected);
+
+        let mut other = NdsFile::open_from_bytes(build_rom(0xa000, 0x20000)).unwrap();
+        assert_ne!(other.content_fingerprint().unwrap(), expected);
+    }
 }
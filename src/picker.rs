@@ -0,0 +1,153 @@
+//! An interactive terminal list to choose which ROMs to trim.
+
+#![warn(clippy::pedantic)]
+
+use std::io::{self, Stderr, Write};
+use std::path::PathBuf;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+/// The help line shown above the list.
+const HELP: &str = "Space: toggle  a: toggle all  Enter: trim selected  q: cancel";
+
+/// A ROM offered for selection.
+pub struct Item {
+    /// The path of the ROM.
+    pub path: PathBuf,
+    /// A description of the ROM, e.g. its title and savings, or why it can't be trimmed.
+    pub label: String,
+    /// Whether the ROM is selected.
+    pub selected: bool,
+}
+
+/// Restores the terminal when dropped, even if drawing fails midway.
+struct Screen {
+    /// The stream the list is drawn on.
+    out: Stderr,
+}
+
+impl Screen {
+    /// Switches the terminal to raw mode and an alternate screen.
+    fn enter() -> io::Result<Self> {
+        let mut out = io::stderr();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(Self { out })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Lets the user toggle which of `items` to trim.
+///
+/// The list is drawn on stderr, so that stdout can still be redirected. Returns the paths of the
+/// selected items, or `None` if the user cancelled.
+pub fn pick(mut items: Vec<Item>) -> io::Result<Option<Vec<PathBuf>>> {
+    let mut screen = Screen::enter()?;
+    let mut current = 0;
+    let mut top = 0;
+
+    loop {
+        let (width, height) = terminal::size()?;
+        let rows = usize::from(height).saturating_sub(2).max(1);
+        if current < top {
+            top = current;
+        } else if current >= top + rows {
+            top = current + 1 - rows;
+        }
+        draw(&mut screen.out, &items, current, top, rows, width.into())?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => current = current.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                current = (current + 1).min(items.len().saturating_sub(1));
+            }
+            KeyCode::PageUp => current = current.saturating_sub(rows),
+            KeyCode::PageDown => current = (current + rows).min(items.len().saturating_sub(1)),
+            KeyCode::Char(' ') => {
+                if let Some(item) = items.get_mut(current) {
+                    item.selected = !item.selected;
+                }
+            }
+            KeyCode::Char('a') => {
+                let select = !items.iter().all(|i| i.selected);
+                for item in &mut items {
+                    item.selected = select;
+                }
+            }
+            KeyCode::Enter => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(None)
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            _ => {}
+        }
+    }
+
+    Ok(Some(
+        items
+            .into_iter()
+            .filter(|i| i.selected)
+            .map(|i| i.path)
+            .collect(),
+    ))
+}
+
+/// Draws `rows` of `items` starting at `top`, highlighting the one at `current`.
+fn draw(
+    out: &mut impl Write,
+    items: &[Item],
+    current: usize,
+    top: usize,
+    rows: usize,
+    width: usize,
+) -> io::Result<()> {
+    let selected = items.iter().filter(|i| i.selected).count();
+    let status = format!("{selected} of {} selected", items.len());
+    queue!(
+        out,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(truncate(HELP, width)),
+        MoveTo(0, 1),
+        Print(truncate(&status, width))
+    )?;
+
+    for (row, (i, item)) in (2..).zip(items.iter().enumerate().skip(top).take(rows)) {
+        let mark = if item.selected { 'x' } else { ' ' };
+        let line = format!("[{mark}] {}", item.label);
+        queue!(out, MoveTo(0, row))?;
+        if i == current {
+            queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(truncate(&line, width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, Print(truncate(&line, width)))?;
+        }
+    }
+
+    out.flush()
+}
+
+/// Shortens `s` to at most `width` characters.
+fn truncate(s: &str, width: usize) -> &str {
+    s.char_indices().nth(width).map_or(s, |(i, _)| &s[..i])
+}
@@ -0,0 +1,25 @@
+//! Helpers for platform-specific properties of the files ROMs are read from.
+
+#![warn(clippy::pedantic)]
+
+use std::path::Path;
+
+/// Checks whether `path` is a block device, following symbolic links.
+///
+/// Block devices, e.g. cartridges attached through a dumper, can be read like files, but not
+/// truncated.
+#[cfg(unix)]
+#[must_use]
+pub fn is_block_device(path: &Path) -> bool {
+    use std::fs;
+    use std::os::unix::fs::FileTypeExt;
+
+    fs::metadata(path).is_ok_and(|m| m.file_type().is_block_device())
+}
+
+/// Returns `false`, since block devices can only be opened like files on Unix-like systems.
+#[cfg(not(unix))]
+#[must_use]
+pub fn is_block_device(_path: &Path) -> bool {
+    false
+}
@@ -0,0 +1,124 @@
+//! Structs to read ROMs served over HTTP(S).
+
+#![warn(clippy::pedantic)]
+
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+
+use crate::nds::Handle;
+
+/// A seekable reader over a file served over HTTP(S).
+///
+/// Data is fetched with range requests, starting from the current position, so only the parts of
+/// the file that are actually read get downloaded.
+pub struct HttpReader {
+    /// The agent performing requests.
+    agent: ureq::Agent,
+    /// The URL of the file.
+    url: String,
+    /// The size of the file.
+    len: u64,
+    /// The current position within the file.
+    pos: u64,
+    /// The body of the response streaming data from `pos`, if a request is in progress.
+    body: Option<Box<dyn Read + Send + Sync>>,
+}
+
+impl HttpReader {
+    /// Opens the file at `url`, retrieving its size.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the request fails, or if the server doesn't report the size of the file.
+    pub fn open(url: &str) -> io::Result<Self> {
+        let agent = ureq::Agent::new();
+        let response = agent.head(url).call().map_err(io::Error::other)?;
+        let len = response
+            .header("Content-Length")
+            .and_then(|l| l.parse().ok())
+            .ok_or_else(|| io::Error::other("server didn't report the file size"))?;
+
+        Ok(Self {
+            agent,
+            url: url.to_owned(),
+            len,
+            pos: 0,
+            body: None,
+        })
+    }
+
+    /// Requests the data starting at the current position.
+    fn request(&self) -> io::Result<Box<dyn Read + Send + Sync>> {
+        let response = self
+            .agent
+            .get(&self.url)
+            .set("Range", &format!("bytes={}-", self.pos))
+            .call()
+            .map_err(io::Error::other)?;
+
+        // A server that ignores the range sends the whole file, which is only usable at the start.
+        if response.status() != 206 && self.pos != 0 {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "server doesn't support range requests",
+            ));
+        }
+
+        Ok(response.into_reader())
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.body.is_none() {
+            self.body = Some(self.request()?);
+        }
+        let n = match &mut self.body {
+            Some(body) => body.read(buf)?,
+            None => 0,
+        };
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "invalid seek position"))?;
+
+        if new_pos != self.pos {
+            self.pos = new_pos;
+            self.body = None;
+        }
+
+        Ok(self.pos)
+    }
+}
+
+impl Handle for HttpReader {
+    fn set_len(&mut self, _len: u64) -> io::Result<()> {
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "remote files can't be trimmed in-place",
+        ))
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            agent: self.agent.clone(),
+            url: self.url.clone(),
+            len: self.len,
+            pos: self.pos,
+            body: None,
+        })
+    }
+}
@@ -0,0 +1,115 @@
+//! Structs to read ROMs compressed in 7z archives.
+
+#![warn(clippy::pedantic)]
+
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use sevenz_rust::{Password, SevenZReader};
+
+use crate::nds::Handle;
+
+/// A ROM decompressed from a 7z archive.
+///
+/// Since 7z archives are usually solid, the whole ROM is decompressed into memory on open.
+pub struct ArchivedRom {
+    /// The decompressed ROM.
+    data: Cursor<Vec<u8>>,
+}
+
+impl ArchivedRom {
+    /// Decompresses the ROM in the 7z archive at `path`.
+    ///
+    /// The archive must contain exactly one file, and must not be password-protected.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the archive can't be read, if it doesn't contain exactly one file, or if it's
+    /// password-protected.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = SevenZReader::open(path, Password::empty()).map_err(into_io)?;
+
+        let files: Vec<_> = reader
+            .archive()
+            .files
+            .iter()
+            .filter(|f| f.has_stream() && !f.is_directory())
+            .collect();
+        let size = match files.as_slice() {
+            [file] => file.size(),
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("archive contains {} files, expected one ROM", files.len()),
+                ))
+            }
+        };
+
+        let capacity =
+            usize::try_from(size).map_err(|e| io::Error::new(ErrorKind::OutOfMemory, e))?;
+        let mut data = Vec::with_capacity(capacity);
+        reader
+            .for_each_entries(|entry, r| {
+                if entry.has_stream() && !entry.is_directory() {
+                    r.read_to_end(&mut data)?;
+                }
+                Ok(true)
+            })
+            .map_err(into_io)?;
+
+        Ok(Self {
+            data: Cursor::new(data),
+        })
+    }
+}
+
+impl Read for ArchivedRom {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.data.read(buf)
+    }
+}
+
+impl Seek for ArchivedRom {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.data.seek(pos)
+    }
+}
+
+impl Handle for ArchivedRom {
+    fn set_len(&mut self, _len: u64) -> io::Result<()> {
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "archived ROMs can't be trimmed in-place",
+        ))
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            data: self.data.clone(),
+        })
+    }
+}
+
+/// Converts `e` into an I/O error with a readable message.
+fn into_io(e: sevenz_rust::Error) -> io::Error {
+    match e {
+        sevenz_rust::Error::Io(e, _) | sevenz_rust::Error::FileOpen(e, _) => e,
+        sevenz_rust::Error::PasswordRequired => password_protected(),
+        sevenz_rust::Error::UnsupportedCompressionMethod(m) if m.starts_with("AES") => {
+            password_protected()
+        }
+        sevenz_rust::Error::UnsupportedCompressionMethod(m) => io::Error::new(
+            ErrorKind::Unsupported,
+            format!("unsupported compression method {m}"),
+        ),
+        e => io::Error::new(ErrorKind::InvalidData, format!("invalid archive: {e}")),
+    }
+}
+
+/// Returns the error for password-protected archives.
+fn password_protected() -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        "password-protected archives aren't supported",
+    )
+}
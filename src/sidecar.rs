@@ -0,0 +1,86 @@
+//! Structs to describe trimmed ROMs in sidecar files.
+
+#![warn(clippy::pedantic)]
+
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use ndstrim::nds::{Handle, NdsFile};
+
+/// The formats sidecar files can be written in.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// Plain text, one `key: value` pair per line
+    Txt,
+    /// A JSON object
+    Json,
+}
+
+impl Format {
+    /// Returns the extension appended to sidecar files.
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Txt => "txt",
+            Format::Json => "json",
+        }
+    }
+}
+
+/// Metadata about a trimmed ROM.
+#[derive(Serialize)]
+pub struct Sidecar {
+    title: String,
+    gamecode: String,
+    region: &'static str,
+    file_size: u64,
+    trimmed_size: u64,
+    simulated: bool,
+}
+
+impl Sidecar {
+    /// Describes `ndsfile`, which was trimmed unless `simulated` is `true`.
+    pub fn new<H: Handle>(ndsfile: &NdsFile<H>, simulated: bool) -> Self {
+        Self {
+            title: ndsfile.title(),
+            gamecode: ndsfile.gamecode(),
+            region: ndsfile.region(),
+            file_size: ndsfile.file_size(),
+            trimmed_size: ndsfile.trimmed_size(),
+            simulated,
+        }
+    }
+
+    /// Writes `self` next to `dest` in the given format, returning the path of the sidecar file.
+    pub fn write(&self, dest: &Path, format: Format) -> io::Result<PathBuf> {
+        let mut path = OsString::from(dest);
+        path.push(".");
+        path.push(format.extension());
+        let path = PathBuf::from(path);
+
+        let contents = match format {
+            Format::Txt => self.to_text(),
+            Format::Json => serde_json::to_string_pretty(self)? + "\n",
+        };
+        fs::write(&path, contents)?;
+
+        Ok(path)
+    }
+
+    /// Renders `self` as plain text.
+    fn to_text(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "title: {}", self.title);
+        let _ = writeln!(s, "gamecode: {}", self.gamecode);
+        let _ = writeln!(s, "region: {}", self.region);
+        let _ = writeln!(s, "file_size: {}", self.file_size);
+        let _ = writeln!(s, "trimmed_size: {}", self.trimmed_size);
+        let _ = writeln!(s, "simulated: {}", self.simulated);
+        s
+    }
+}
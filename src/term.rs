@@ -0,0 +1,62 @@
+//! Functions to style terminal output.
+
+#![warn(clippy::pedantic)]
+
+use std::fmt::Display;
+
+#[cfg(feature = "color")]
+pub use anstream::{eprintln, println};
+#[cfg(not(feature = "color"))]
+pub use std::{eprintln, println};
+
+use crate::cli::ColorChoice;
+
+/// The kinds of messages that can be styled.
+#[derive(Clone, Copy)]
+pub enum Tone {
+    /// A file was trimmed, or would be.
+    Success,
+    /// A file was skipped, or something looks suspicious.
+    Warning,
+    /// Something failed.
+    Failure,
+}
+
+/// Sets whether output is colored.
+///
+/// With [`ColorChoice::Auto`], colors are only used if the output is a terminal and `NO_COLOR`
+/// isn't set.
+pub fn init(choice: ColorChoice) {
+    #[cfg(feature = "color")]
+    match choice {
+        ColorChoice::Auto => anstream::ColorChoice::Auto,
+        ColorChoice::Always => anstream::ColorChoice::Always,
+        ColorChoice::Never => anstream::ColorChoice::Never,
+    }
+    .write_global();
+
+    #[cfg(not(feature = "color"))]
+    let _ = choice;
+}
+
+/// Styles `text` according to `tone`.
+pub fn paint(tone: Tone, text: impl Display) -> String {
+    #[cfg(feature = "color")]
+    {
+        use anstyle::{AnsiColor, Style};
+
+        let color = match tone {
+            Tone::Success => AnsiColor::Green,
+            Tone::Warning => AnsiColor::Yellow,
+            Tone::Failure => AnsiColor::Red,
+        };
+        let style = Style::new().fg_color(Some(color.into()));
+        format!("{}{}{}", style.render(), text, style.render_reset())
+    }
+
+    #[cfg(not(feature = "color"))]
+    {
+        let _ = tone;
+        text.to_string()
+    }
+}
@@ -0,0 +1,301 @@
+//! Abstractions over the filesystem operations performed while processing batches of files.
+
+#![warn(clippy::pedantic)]
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use ndstrim::platform;
+
+/// The filesystem operations used to find inputs and prepare outputs.
+///
+/// ROM data itself is read and written through [`ndstrim::Handle`], this only covers the
+/// orchestration around it, so that it can be exercised without touching a real filesystem.
+pub trait FileSystem {
+    /// Returns the paths of the entries in the directory at `path`, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Checks whether `path` is a directory, following symbolic links.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Checks whether something exists at `path`.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Checks whether `path` is a block device, following symbolic links.
+    fn is_block_device(&self, path: &Path) -> bool;
+
+    /// Checks whether `path` is a symbolic link, without following it.
+    fn is_symlink(&self, path: &Path) -> bool;
+
+    /// Returns the device and inode numbers of `path`, following symbolic links, if available.
+    fn file_id(&self, path: &Path) -> Option<(u64, u64)>;
+
+    /// Returns the size of the file at `path`, following symbolic links.
+    fn len(&self, path: &Path) -> io::Result<u64>;
+
+    /// Returns the last modification time of the file at `path`, following symbolic links.
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+
+    /// Creates the directory at `path` and any missing parents.
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Creates an empty file at `path`, failing if it already exists.
+    fn create_new(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes the file at `path`.
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Renames the file at `from` to `to`, replacing it if it exists.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+/// The real filesystem, accessed through [`std::fs`].
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_block_device(&self, path: &Path) -> bool {
+        platform::is_block_device(path)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+
+    fn file_id(&self, path: &Path) -> Option<(u64, u64)> {
+        file_id(path)
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        fs::metadata(path).map(|m| m.len())
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn create_new(&self, path: &Path) -> io::Result<()> {
+        File::options()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map(drop)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+}
+
+/// Returns the device and inode numbers of `path`, if available.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+/// Returns `None`, since identifying files is only supported on Unix-like systems.
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// An in-memory filesystem, to exercise the orchestration around ROMs in tests.
+///
+/// Files only have a size. Symbolic links are only followed as the last component of a path.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemFs {
+    /// The entries, by absolute or relative path.
+    entries: std::cell::RefCell<std::collections::BTreeMap<PathBuf, Entry>>,
+    /// The identifier given to the next entry.
+    next_id: std::cell::Cell<u64>,
+}
+
+/// An entry of a [`MemFs`], along with its identifier.
+#[cfg(test)]
+#[derive(Clone)]
+enum Entry {
+    Dir(u64),
+    File(u64, u64),
+    Symlink(PathBuf),
+}
+
+#[cfg(test)]
+impl MemFs {
+    /// Adds a directory at `path`, along with its missing parents.
+    pub fn add_dir(&self, path: impl AsRef<Path>) {
+        self.create_dir_all(path.as_ref()).unwrap();
+    }
+
+    /// Adds a file of `len` bytes at `path`, along with its missing parents.
+    pub fn add_file(&self, path: impl AsRef<Path>, len: u64) {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.add_dir(parent);
+        }
+        let id = self.new_id();
+        self.entries
+            .borrow_mut()
+            .insert(path.to_path_buf(), Entry::File(id, len));
+    }
+
+    /// Adds a symbolic link at `path` pointing to `target`.
+    pub fn add_symlink(&self, path: impl AsRef<Path>, target: impl AsRef<Path>) {
+        self.entries.borrow_mut().insert(
+            path.as_ref().to_path_buf(),
+            Entry::Symlink(target.as_ref().to_path_buf()),
+        );
+    }
+
+    /// Checks whether there's a regular file at `path`, without following symbolic links.
+    pub fn is_file(&self, path: impl AsRef<Path>) -> bool {
+        matches!(self.entries.borrow().get(path.as_ref()), Some(Entry::File(..)))
+    }
+
+    /// Returns the paths of every entry, in sorted order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.borrow().keys().cloned().collect()
+    }
+
+    /// Returns a fresh entry identifier.
+    fn new_id(&self) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+
+    /// Returns the entry at `path`, following symbolic links, along with its resolved path.
+    fn resolve(&self, path: &Path) -> Option<(PathBuf, Entry)> {
+        let mut path = path.to_path_buf();
+        // Bound the chain of links, as they may form a cycle.
+        for _ in 0..8 {
+            match self.entries.borrow().get(&path)? {
+                Entry::Symlink(target) => path = target.clone(),
+                entry => return Some((path, entry.clone())),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+impl FileSystem for MemFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let Some((dir, Entry::Dir(_))) = self.resolve(path) else {
+            return Err(io::ErrorKind::NotFound.into());
+        };
+
+        Ok(self
+            .entries
+            .borrow()
+            .keys()
+            .filter(|p| p.parent() == Some(&dir))
+            .filter_map(|p| p.file_name())
+            .map(|name| path.join(name))
+            .collect())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.resolve(path), Some((_, Entry::Dir(_))))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    fn is_block_device(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        matches!(self.entries.borrow().get(path), Some(Entry::Symlink(_)))
+    }
+
+    fn file_id(&self, path: &Path) -> Option<(u64, u64)> {
+        match self.resolve(path)? {
+            (_, Entry::Dir(id) | Entry::File(id, _)) => Some((0, id)),
+            (_, Entry::Symlink(_)) => None,
+        }
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        match self.resolve(path) {
+            Some((_, Entry::File(_, len))) => Ok(len),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        self.len(path).map(|_| SystemTime::UNIX_EPOCH)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        for dir in path.ancestors().filter(|p| !p.as_os_str().is_empty()) {
+            if !self.is_dir(dir) {
+                let id = self.new_id();
+                self.entries
+                    .borrow_mut()
+                    .insert(dir.to_path_buf(), Entry::Dir(id));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_new(&self, path: &Path) -> io::Result<()> {
+        if self.exists(path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        if path.parent().is_some_and(|p| !p.as_os_str().is_empty() && !self.is_dir(p)) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        self.add_file(path, 0);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.entries.borrow_mut();
+        match entries.get(path) {
+            Some(Entry::File(..) | Entry::Symlink(_)) => {
+                entries.remove(path);
+                Ok(())
+            }
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut entries = self.entries.borrow_mut();
+        if matches!(entries.get(to), Some(Entry::Dir(_))) {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+        let entry = entries.remove(from).ok_or(io::ErrorKind::NotFound)?;
+        entries.insert(to.to_path_buf(), entry);
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+//! Functions to find ROMs within directories and weed out duplicate paths.
+
+#![warn(clippy::pedantic)]
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::vfs::FileSystem;
+
+/// Extensions of the files picked up while walking directories.
+const EXTENSIONS: [&str; 2] = ["nds", "dsi"];
+
+/// The files found while walking directories, and the errors encountered.
+#[derive(Default)]
+pub struct Walk {
+    /// The files found, in sorted order within each directory.
+    pub files: Vec<PathBuf>,
+    /// The paths that couldn't be read, along with the reason.
+    pub errors: Vec<(PathBuf, io::Error)>,
+    /// The device and inode numbers of the directories visited so far.
+    visited: HashSet<(u64, u64)>,
+}
+
+/// Expands the directories in `roots` into the ROMs they contain.
+///
+/// Files in `roots` are kept as they are. Directories are descended into at most `max_depth`
+/// levels below the root, where `0` only picks up the files directly inside it.
+///
+/// Symbolically linked files are always picked up, while symbolically linked directories are
+/// skipped unless `follow_symlinks` is `true`. Directories are then only visited once, to break
+/// cycles, which requires identifying them: elsewhere than on Unix-like systems, linked
+/// directories are still skipped.
+pub fn walk(
+    fs: &impl FileSystem,
+    roots: &[PathBuf],
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+) -> Walk {
+    let mut walk = Walk::default();
+    for root in roots {
+        if fs.is_dir(root) {
+            walk.visit(fs, root, 0, max_depth, follow_symlinks);
+        } else {
+            walk.files.push(root.clone());
+        }
+    }
+
+    walk
+}
+
+impl Walk {
+    /// Collects the ROMs in `dir`, which is `depth` levels below the root.
+    fn visit(
+        &mut self,
+        fs: &impl FileSystem,
+        dir: &Path,
+        depth: usize,
+        max_depth: Option<usize>,
+        follow_symlinks: bool,
+    ) {
+        if let (true, Some(id)) = (follow_symlinks, fs.file_id(dir)) {
+            if !self.visited.insert(id) {
+                return;
+            }
+        }
+
+        let mut entries = match fs.read_dir(dir) {
+            Ok(e) => e,
+            Err(e) => {
+                self.errors.push((dir.to_path_buf(), e));
+                return;
+            }
+        };
+        entries.sort();
+
+        for path in entries {
+            if fs.is_dir(&path) {
+                let followed = !fs.is_symlink(&path)
+                    || (follow_symlinks && fs.file_id(&path).is_some());
+                if followed && max_depth.is_none_or(|max| depth < max) {
+                    self.visit(fs, &path, depth + 1, max_depth, follow_symlinks);
+                }
+            } else if is_rom(&path) {
+                self.files.push(path);
+            }
+        }
+    }
+}
+
+/// Removes paths that refer to the same file as an earlier one, e.g. hard links.
+///
+/// Returns the remaining paths, and pairs of each removed path with the one it aliases. Only
+/// Unix-like systems are supported, elsewhere every path is kept.
+pub fn dedup_links(
+    fs: &impl FileSystem,
+    files: Vec<PathBuf>,
+) -> (Vec<PathBuf>, Vec<(PathBuf, PathBuf)>) {
+    let mut seen: HashMap<(u64, u64), usize> = HashMap::new();
+    let mut unique: Vec<PathBuf> = Vec::with_capacity(files.len());
+    let mut aliases = Vec::new();
+
+    for path in files {
+        if let Some(id) = fs.file_id(&path) {
+            if let Some(&i) = seen.get(&id) {
+                aliases.push((path, unique[i].clone()));
+                continue;
+            }
+            seen.insert(id, unique.len());
+        }
+        unique.push(path);
+    }
+
+    (unique, aliases)
+}
+
+/// Checks whether `path` looks like a trimmed copy written with `extension`, e.g. `foo.trim.nds`.
+///
+/// Extensions of ROMs themselves, e.g. `nds`, never match, as every ROM would then look like a
+/// copy.
+pub fn is_trimmed_copy(path: &Path, extension: &str) -> bool {
+    if EXTENSIONS.iter().any(|x| extension.eq_ignore_ascii_case(x)) {
+        return false;
+    }
+
+    let suffix = format!(".{extension}").to_ascii_lowercase();
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.len() > suffix.len() && n.to_ascii_lowercase().ends_with(&suffix))
+}
+
+/// Checks whether `path` has the extension of a ROM.
+fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemFs;
+
+    /// Builds a tree of ROMs and other files, two levels deep.
+    fn tree() -> MemFs {
+        let fs = MemFs::default();
+        fs.add_file("roms/b.nds", 0x100);
+        fs.add_file("roms/a.NDS", 0x100);
+        fs.add_file("roms/readme.txt", 0x10);
+        fs.add_file("roms/dsi/c.dsi", 0x100);
+        fs.add_file("roms/dsi/old/d.nds", 0x100);
+        fs
+    }
+
+    #[test]
+    fn walk_finds_roms_in_sorted_order() {
+        let walk = walk(&tree(), &[PathBuf::from("roms")], None, false);
+
+        assert_eq!(
+            walk.files,
+            [
+                "roms/a.NDS",
+                "roms/b.nds",
+                "roms/dsi/c.dsi",
+                "roms/dsi/old/d.nds"
+            ]
+            .map(PathBuf::from)
+        );
+        assert!(walk.errors.is_empty());
+    }
+
+    #[test]
+    fn walk_stops_at_max_depth() {
+        let walk = walk(&tree(), &[PathBuf::from("roms")], Some(1), false);
+
+        assert_eq!(
+            walk.files,
+            ["roms/a.NDS", "roms/b.nds", "roms/dsi/c.dsi"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn walk_keeps_roots_that_arent_directories() {
+        let fs = tree();
+        fs.add_symlink("missing", "nowhere");
+        let roots = ["roms/readme.txt", "missing"].map(PathBuf::from);
+
+        let walk = walk(&fs, &roots, None, false);
+
+        assert_eq!(walk.files, roots);
+    }
+
+    #[test]
+    fn walk_follows_linked_directories_only_once() {
+        let fs = tree();
+        fs.add_symlink("roms/dsi/loop", "roms");
+        fs.add_symlink("roms/linked", "roms/dsi/old");
+        let roots = [PathBuf::from("roms")];
+
+        let skipped = walk(&fs, &roots, None, false);
+        let followed = walk(&fs, &roots, None, true);
+
+        assert_eq!(skipped.files.len(), 4);
+        // The linked directory was already visited through its real path, and the loop is cut
+        // short.
+        assert_eq!(
+            followed.files,
+            [
+                "roms/a.NDS",
+                "roms/b.nds",
+                "roms/dsi/c.dsi",
+                "roms/dsi/old/d.nds"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn dedup_links_drops_aliases() {
+        let fs = tree();
+        fs.add_symlink("alias.nds", "roms/b.nds");
+        let files = ["roms/b.nds", "alias.nds", "roms/a.NDS"].map(PathBuf::from);
+
+        let (unique, aliases) = dedup_links(&fs, files.to_vec());
+
+        assert_eq!(unique, ["roms/b.nds", "roms/a.NDS"].map(PathBuf::from));
+        assert_eq!(
+            aliases,
+            [(PathBuf::from("alias.nds"), PathBuf::from("roms/b.nds"))]
+        );
+    }
+}